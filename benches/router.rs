@@ -0,0 +1,60 @@
+//! Route lookup as the number of routes grows.
+//!
+//! Run with `cargo bench --bench router`.
+
+extern crate direkuta;
+
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+const REQUESTS: u32 = 20_000;
+
+/// The parameters routes end with, matched in the tree, in the tree with a regex
+/// and with the set of patterns across segments.
+const PARAMS: [&str; 3] = ["<id>", "<id:([0-9]+)>", "<id:(.+)>"];
+
+/// An app with `count` routes, each ending with the parameter.
+fn app(count: usize, param: &'static str) -> Direkuta {
+    Direkuta::new().route(move |r| {
+        for i in 0..count {
+            let path = format!("/route{}/{}", i, param);
+
+            r.get(path, |_, _, c| Response::new().with_body(c.get("id")).build());
+        }
+    })
+}
+
+/// Average time for a request to the last route added.
+fn bench(count: usize, param: &'static str) -> Duration {
+    let mut client = TestClient::new(app(count, param));
+    let path = format!("/route{}/42", count - 1);
+
+    for _ in 0..1_000 {
+        let _ = client.get(path.as_str()).send().expect("To send request");
+    }
+
+    let start = Instant::now();
+
+    for _ in 0..REQUESTS {
+        let _ = client.get(path.as_str()).send().expect("To send request");
+    }
+
+    start.elapsed() / REQUESTS
+}
+
+fn main() {
+    println!("| Routes | `<id>` segments | Regex segments | Patterns across segments |");
+    println!("|-------:|----------------:|---------------:|-------------------------:|");
+
+    for &count in &[1, 10, 100, 500, 1000] {
+        println!(
+            "| {} | {:.2?} | {:.2?} | {:.2?} |",
+            count,
+            bench(count, PARAMS[0]),
+            bench(count, PARAMS[1]),
+            bench(count, PARAMS[2])
+        );
+    }
+}
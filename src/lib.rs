@@ -105,6 +105,8 @@ extern crate http;
 extern crate hyper;
 extern crate indexmap;
 extern crate regex;
+extern crate regex_syntax;
+extern crate tokio;
 extern crate tokio_fs;
 
 #[cfg(feature = "json")]
@@ -118,20 +120,46 @@ extern crate serde_json;
 #[cfg(feature = "html")]
 extern crate tera;
 
+#[cfg(unix)]
+extern crate tokio_uds;
+
+#[cfg(feature = "tls")]
+extern crate ring;
+#[cfg(feature = "tls")]
+extern crate rustls;
+#[cfg(feature = "tls")]
+extern crate x509_parser;
+
 use std::any::{Any, TypeId};
 use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
+use std::mem;
+use std::net::{AddrParseError, SocketAddr, TcpListener, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-
-use futures::{future, Future};
-use http::{request, response};
-use hyper::header::{self, HeaderMap, HeaderValue};
-use hyper::service::{NewService, Service};
-use hyper::{rt, Body, Method, Server, StatusCode, Uri, Version};
+use std::time::{Duration, Instant};
+
+use futures::future::Shared;
+use futures::{future, try_ready, Async, Future, Poll, Stream};
+use http::{request, response, HttpTryFrom};
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
+use hyper::body::Payload;
+use hyper::service::{make_service_fn, NewService, Service};
+use hyper::{Body, Chunk, Method, Server, StatusCode, Uri, Version};
 use indexmap::IndexMap;
-use regex::Regex;
+use regex::{Regex, RegexSet};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use tokio::runtime::current_thread::Runtime as CurrentRuntime;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener as TokioTcpListener, TcpStream as TokioTcpStream};
+use tokio::reactor::Handle;
+use tokio::runtime::Builder as RuntimeBuilder;
+use tokio::timer::{Delay, Timeout};
 
 #[cfg(feature = "json")]
 use serde::Serialize;
@@ -139,17 +167,64 @@ use serde::Serialize;
 #[cfg(feature = "html")]
 use tera::Tera;
 
+#[cfg(feature = "tls")]
+use std::net::IpAddr;
+
+#[cfg(feature = "tls")]
+use futures::stream::FuturesUnordered;
+#[cfg(feature = "tls")]
+use ring::digest;
+#[cfg(feature = "tls")]
+use rustls::internal::pemfile;
+#[cfg(feature = "tls")]
+use rustls::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, NoClientAuth,
+    RootCertStore, ServerConfig, ServerSession, Session,
+};
+
+#[cfg(unix)]
+use std::env;
+#[cfg(unix)]
+use std::fs::{self, Permissions};
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process;
+
+#[cfg(unix)]
+use tokio_uds::{UnixListener as TokioUnixListener, UnixStream as TokioUnixStream};
+
 /// The Direkuta web server itself.
 pub struct Direkuta {
     config: Arc<Config>,
     /// Store state as its own type.
     state: Arc<State>,
     /// Stores middleware, to be later used in Service::call.
-    middle: Arc<IndexMap<TypeId, Box<Middle + Send + Sync + 'static>>>,
+    middle: Arc<IndexMap<TypeId, Box<dyn Middle + Send + Sync + 'static>>>,
     /// The router, it knows where a url is meant to go.
     routes: Arc<Router>,
+    /// TLS settings, loaded from Config when the server is built.
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<ServerConfig>>,
+    /// The verified client certificate of the connection this service is handling.
+    #[cfg(feature = "tls")]
+    peer: Option<Arc<PeerCertificate>>,
+    /// The requests on the connection this service is handling.
+    activity: Option<Arc<Activity>>,
+    /// Requests being handled across all connections, counted when `Config::max_concurrency` is set.
+    in_flight: Arc<AtomicUsize>,
+    /// Turns errors from handlers in to responses, `default_error_handler` is used when unset.
+    error_handler: Option<Arc<ErrorHandler>>,
 }
 
+type ErrorHandler = dyn Fn(DireError) -> Response + Send + Sync + 'static;
+
 impl Direkuta {
     /// Constructs a new Direkuta.
     ///
@@ -180,23 +255,18 @@ impl Direkuta {
 
         c(&mut config);
 
-        #[allow(unused_mut)]
-        let mut state = State::new();
-
-        #[cfg(feature = "html")]
-        state.set(match Tera::parse(&format!("{}/**/*", config.template_path)) {
-            Ok(t) => t,
-            Err(e) => {
-                println!("Parsing error(s): {}", e);
-                ::std::process::exit(1);
-            }
-        });
-
         Self {
             config: Arc::new(config),
-            state: Arc::new(state),
+            state: Arc::new(State::new()),
             middle: Arc::new(IndexMap::new()),
             routes: Arc::new(Router::default()),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            peer: None,
+            activity: None,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            error_handler: None,
         }
     }
 
@@ -246,6 +316,31 @@ impl Direkuta {
         self
     }
 
+    /// Set how errors returned by handlers are turned in to responses.
+    ///
+    /// By default errors are answered with the status from `DireError::status`,
+    /// only the message of a `DireError::Status` is sent to the client.
+    /// Handlers that run past their timeout end with a `DireError::Timeout`, which the default
+    /// answers with `Config::timeout_status`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::new()
+    ///     .error_handler(|e| match e {
+    ///         DireError::Status(status, message) => Response::new()
+    ///             .with_status(status.as_u16())
+    ///             .with_body(format!("Error: {}", message)),
+    ///         _ => Response::new().with_status(500).with_body("Error: Something went wrong"),
+    ///     });
+    /// ```
+    #[inline]
+    pub fn error_handler(mut self, handler: impl Fn(DireError) -> Response + Send + Sync + 'static) -> Self {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
     /// Create new router as a closure.
     ///
     /// # Examples
@@ -262,11 +357,78 @@ impl Direkuta {
         let mut route_builder = Router::new();
 
         route(&mut route_builder);
+        route_builder.build();
         self.routes = Arc::new(route_builder);
 
         self
     }
 
+    /// Prepare the server for running.
+    ///
+    /// This parses templates, loads TLS certificates and checks that every route is valid,
+    /// it is called by `try_run` and `run_until` so there is no need to call it yourself.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let result = Direkuta::new()
+    ///     .route(|r| {
+    ///         r.get("/<id:([0-9]+>", |_, _, _| {
+    ///             Response::new().build()
+    ///         });
+    ///     })
+    ///     .build();
+    ///
+    /// assert!(result.is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a `Config` setting is invalid, if the templates could not be parsed,
+    /// if the TLS certificate or key could not be loaded, if a route pattern is not a valid regex
+    /// or if a route is broken, like one that can never be matched or that uses an id twice.
+    /// When several routes are broken they are all returned in a `DireError::Routes`.
+    pub fn build(mut self) -> Result<Self, DireError> {
+        if self.config.workers == Some(0) {
+            return Err(DireError::Config("The worker count must be at least 1".to_string()));
+        }
+
+        let state = Arc::get_mut(&mut self.state).expect("Cannot get_mut on state");
+
+        state.set(self.routes.urls.clone());
+
+        #[cfg(feature = "html")]
+        {
+            if state.try_get::<Tera>().is_none() {
+                state.set(Tera::parse(&format!("{}/**/*", self.config.template_path))?);
+            }
+
+            if let Some(tera) = state.try_get_mut::<Tera>() {
+                tera.register_function("url_for", self.routes.urls.tera_function());
+            }
+        }
+
+        #[cfg(feature = "tls")]
+        {
+            if self.tls.is_none() {
+                self.tls = self.config.tls_config()?;
+            }
+        }
+
+        let routes = Arc::get_mut(&mut self.routes).expect("Cannot get_mut on routes");
+
+        let mut errors = mem::take(&mut routes.errors);
+
+        match errors.len() {
+            0 => {}
+            1 => return Err(errors.remove(0)),
+            _ => return Err(DireError::Routes(errors)),
+        }
+
+        Ok(self)
+    }
+
     /// Run server as a Hyper server.
     ///
     /// # Examples
@@ -278,394 +440,2730 @@ impl Direkuta {
     ///     .run("0.0.0.0:3000");
     /// ```
     ///
+    /// # Panics
+    ///
+    /// If the server fails to start, use `try_run` to handle the error yourself.
+    ///
     /// # Errors
     ///
     /// If any errors come from the server they will be printed to the console.
     #[inline]
     pub fn run(self, addr: &str) {
-        let address = addr.parse().expect("Address not a valid socket address");
-        let server = Server::bind(&address)
-            .serve(self)
-            .map_err(|e| eprintln!("server error: {}", e));
+        if let Err(e) = self.try_run(addr) {
+            panic!("Unable to start server: {}", e);
+        }
+    }
 
-        println!("Direkuta listening on http://{}", addr);
+    /// Run server as a Hyper server, returning any error that stopped it from starting.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// # use direkuta::prelude::*;
+    /// // Not tested due to the fact that its a web server.
+    /// if let Err(e) = Direkuta::new().try_run("0.0.0.0:3000") {
+    ///     eprintln!("{}", e);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address is invalid or cannot be bound, if the templates
+    /// could not be parsed, if a route pattern is not a valid regex or if the runtime
+    /// could not be created.
+    #[inline]
+    pub fn try_run(self, addr: &str) -> Result<(), DireError> {
+        self.run_until(addr, future::empty())
+    }
+
+    /// Run server as a Hyper server until the given signal resolves.
+    ///
+    /// Once the signal resolves the server stops accepting new connections and
+    /// waits for active requests to finish, for at most the configured shutdown timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// # extern crate direkuta;
+    /// # extern crate futures;
+    /// # use direkuta::prelude::*;
+    /// # use futures::Future;
+    /// # fn main() {
+    /// // Not tested due to the fact that its a web server.
+    /// let (tx, rx) = futures::sync::oneshot::channel::<()>();
+    ///
+    /// // Call `tx.send(())` from elsewhere to stop the server.
+    /// Direkuta::new()
+    ///     .run_until("0.0.0.0:3000", rx.map_err(|_| ()))
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server could not be started, see `try_run`.
+    ///
+    /// Any errors that come from the running server will be printed to the console.
+    pub fn run_until(
+        self,
+        addr: &str,
+        signal: impl Future<Item = (), Error = ()> + Send + 'static,
+    ) -> Result<(), DireError> {
+        let address: SocketAddr = addr.parse()?;
 
-        rt::run(server);
+        self.bind(address)?.run_until(signal)
     }
-}
 
-impl Default for Direkuta {
-    fn default() -> Self {
-        #[allow(unused_mut)]
-        let mut state = State::new();
+    /// Create the server as a future, to be spawned on an existing Tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// # extern crate direkuta;
+    /// # extern crate futures;
+    /// # extern crate tokio;
+    /// # use direkuta::prelude::*;
+    /// # use futures::Future;
+    /// # fn main() {
+    /// // Not tested due to the fact that its a web server.
+    /// let server = Direkuta::new()
+    ///     .serve("0.0.0.0:3000")
+    ///     .unwrap();
+    ///
+    /// tokio::run(server.map_err(|e| eprintln!("{}", e)));
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server could not be started, see `try_run`.
+    pub fn serve(
+        self,
+        addr: &str,
+    ) -> Result<Box<dyn Future<Item = (), Error = DireError> + Send + 'static>, DireError> {
+        let address: SocketAddr = addr.parse()?;
 
-        #[cfg(feature = "html")]
-        state.set(match Tera::parse("templates/**/*") {
-            Ok(t) => t,
-            Err(e) => {
-                println!("Parsing error(s): {}", e);
-                ::std::process::exit(1);
-            }
-        });
+        self.bind(address)?.serve()
+    }
 
-        Self {
-            config: Arc::new(Config::new()),
-            state: Arc::new(state),
-            middle: Arc::new(IndexMap::new()),
-            routes: Arc::new(Router::default()),
-        }
+    /// Bind the server to an address without running it.
+    ///
+    /// Binding to port 0 lets the OS pick a free port, which can then be read from `Bound::local_addr`.
+    /// More addresses can be added with `Bound::bind`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let bound = Direkuta::new()
+    ///     .bind("127.0.0.1:0")
+    ///     .unwrap();
+    ///
+    /// assert_ne!(bound.local_addr().unwrap().port(), 0);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound or if the server could not be built,
+    /// see `Direkuta::build`.
+    pub fn bind(self, addr: impl ToSocketAddrs) -> Result<Bound, DireError> {
+        self.bound()?.bind(addr)
     }
-}
 
-impl NewService for Direkuta {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = DireError;
-    type InitError = DireError;
-    type Service = Direkuta;
-    type Future = Box<Future<Item = Self::Service, Error = Self::InitError> + Send>;
+    /// Use an already bound listener for the server without running it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// use std::net::TcpListener;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = listener.local_addr().unwrap();
+    ///
+    /// let bound = Direkuta::new()
+    ///     .listen(listener)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(bound.local_addr(), Some(addr));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener has no local address or if the server could not be built,
+    /// see `Direkuta::build`.
+    pub fn listen(self, listener: TcpListener) -> Result<Bound, DireError> {
+        self.bound()?.listen(listener)
+    }
 
-    fn new_service(&self) -> Self::Future {
-        Box::new(future::ok(Self {
+    /// Bind the server to a Unix socket path without running it.
+    ///
+    /// An existing socket at the path is replaced, any other file is reported as an error.
+    /// With `Config::unix_socket_mode` the socket only appears at the path once it has those
+    /// permissions. The socket is removed when the server stops, unless another took its place.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// # use direkuta::prelude::*;
+    /// // Not tested due to the fact that its a web server.
+    /// Direkuta::config(|c| {
+    ///     c.unix_socket_mode(0o660);
+    /// }).bind_unix("/run/direkuta.sock")
+    ///     .unwrap()
+    ///     .run()
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket cannot be bound or if the server could not be built,
+    /// see `Direkuta::build`.
+    #[cfg(unix)]
+    pub fn bind_unix(self, path: impl AsRef<Path>) -> Result<Bound, DireError> {
+        self.bound()?.bind_unix(path)
+    }
+
+    /// Use an already bound Unix listener for the server without running it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server could not be built, see `Direkuta::build`.
+    #[cfg(unix)]
+    pub fn listen_unix(self, listener: UnixListener) -> Result<Bound, DireError> {
+        Ok(self.bound()?.listen_unix(listener))
+    }
+
+    /// Use the sockets passed by systemd socket activation through `LISTEN_FDS`.
+    ///
+    /// Both TCP and Unix sockets are supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no sockets were passed to this process, if `LISTEN_FDS` is not a
+    /// valid count or if the server could not be built, see `Direkuta::build`.
+    #[cfg(unix)]
+    pub fn listen_fds(self) -> Result<Bound, DireError> {
+        self.bound()?.listen_fds()
+    }
+
+    /// Build the server, without any listeners yet.
+    fn bound(self) -> Result<Bound, DireError> {
+        Ok(Bound {
+            app: self.build()?,
+            listeners: Vec::new(),
+        })
+    }
+
+    /// Share the server's settings, router, and state with a new service.
+    fn share(&self) -> Self {
+        Self {
             config: self.config.clone(),
             state: self.state.clone(),
             middle: self.middle.clone(),
             routes: self.routes.clone(),
-        }))
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
+            #[cfg(feature = "tls")]
+            peer: None,
+            activity: None,
+            in_flight: self.in_flight.clone(),
+            error_handler: self.error_handler.clone(),
+        }
     }
 }
 
-impl Service for Direkuta {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = DireError;
-    type Future = Box<Future<Item = response::Response<Self::ResBody>, Error = Self::Error> + Send>;
-
-    fn call(&mut self, req: request::Request<Self::ReqBody>) -> Self::Future {
-        let path = req.uri().path().to_owned();
-        let (parts, body) = req.into_parts();
-        let mut req = Request::new(body, parts);
+/// Take the sockets passed to this process by the service manager.
+#[cfg(unix)]
+fn inherited_listeners() -> Result<Vec<Listener>, DireError> {
+    /// The first passed socket, after stdin, stdout, and stderr.
+    const LISTEN_FDS_START: RawFd = 3;
 
-        for (_, before) in self.middle.iter() {
-            before.run(&mut req);
-        }
+    let pid = env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok());
 
-        match self.routes.recognize(&req.method(), &path) {
-            Ok((handler, cap)) => handler(req, self.state.clone(), cap),
-            Err(code) => Response::new().with_status(code.as_u16()).build(),
-        }
+    if pid != Some(process::id()) {
+        return Ok(Vec::new());
     }
-}
 
-/// Internal configuration for the server.
-///
-/// Allows finer control of preset variables like template and static path.
-pub struct Config {
-    template_path: String,
-    static_path: String,
-}
+    let count = env::var("LISTEN_FDS").ok();
 
-impl Config {
-    fn new() -> Self {
-        Self::default()
+    // The sockets are meant for this process only, not its children.
+    for var in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(var);
     }
 
-    /// Set the path for templates, defaults to "templates".
-    #[inline]
-    pub fn template_path(&mut self, path: impl Into<String>) {
-        self.template_path = path.into();
-    }
+    let count = match count {
+        Some(count) => count
+            .parse::<u32>()
+            .ok()
+            .filter(|&count| count <= (RawFd::MAX - LISTEN_FDS_START) as u32)
+            .ok_or_else(|| {
+                DireError::Config(format!("`LISTEN_FDS` is not a valid socket count: `{}`", count))
+            })? as RawFd,
+        None => 0,
+    };
 
-    /// Set the path for static files, defaults to "static".
-    #[inline]
-    pub fn static_path(&mut self, path: impl Into<String>) {
-        self.static_path = path.into();
-    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // The service manager hands these sockets to this process, nothing else owns them.
+            #[allow(unsafe_code)]
+            let listener = unsafe { UnixListener::from_raw_fd(fd) };
+
+            // Passed sockets are not closed on exec, their duplicates are,
+            // so they don't leak in to child processes.
+            if listener.local_addr().is_ok() {
+                return Ok(Listener::Unix(listener.try_clone()?, None));
+            }
+
+            #[allow(unsafe_code)]
+            let listener = unsafe { TcpListener::from_raw_fd(listener.into_raw_fd()) };
+            let addr = listener.local_addr()?;
+
+            Ok(Listener::Tcp(listener.try_clone()?, addr))
+        }).collect::<io::Result<_>>()
+        .map_err(DireError::Bind)
 }
 
-impl Default for Config {
+impl Default for Direkuta {
     fn default() -> Self {
         Self {
-            template_path: "templates".to_string(),
-            static_path: "static".to_string(),
+            config: Arc::new(Config::new()),
+            state: Arc::new(State::new()),
+            middle: Arc::new(IndexMap::new()),
+            routes: Arc::new(Router::default()),
+            #[cfg(feature = "tls")]
+            tls: None,
+            #[cfg(feature = "tls")]
+            peer: None,
+            activity: None,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            error_handler: None,
         }
     }
 }
 
-/// Wrapper around common and library error types.
+/// A Direkuta server that has bound its addresses but is not yet running.
 ///
-/// You should not have to create your own error type.
-#[derive(Debug)]
-pub enum DireError {
-    /// Any error that originates from Hyper.
-    Hyper(hyper::Error),
-    /// General error, for use when no error type exists.
-    Other(String),
-    /// No type found in State.
-    StateNotFound,
+/// Created with `Direkuta::bind` or `Direkuta::listen`, more listeners can be added with
+/// `Bound::bind` and `Bound::listen`. All listeners share the same router, state, and middleware.
+pub struct Bound {
+    app: Direkuta,
+    listeners: Vec<Listener>,
 }
 
-impl std::fmt::Display for DireError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
-            DireError::Hyper(ref e) => write!(f, "(DireError [Hyper] {})", e),
-            DireError::Other(ref e) => write!(f, "(DireError [Other] {})", e),
-            DireError::StateNotFound => write!(f, "(DireError [State] Key Not Found)"),
-        }
-    }
+/// A socket the server accepts connections on.
+enum Listener {
+    Tcp(TcpListener, SocketAddr),
+    /// The file is set when the socket was created by `bind_unix`.
+    #[cfg(unix)]
+    Unix(UnixListener, Option<SocketFile>),
 }
 
-impl Error for DireError {
-    fn description(&self) -> &str {
-        match *self {
-            DireError::Hyper(ref e) => e.description(),
-            DireError::Other(ref e) => e,
-            DireError::StateNotFound => "Key not found",
-        }
-    }
+/// The file of a socket created by `bind_unix`, removed once the server is done with it.
+#[cfg(unix)]
+struct SocketFile {
+    path: PathBuf,
+    /// Tells this socket apart from one bound in its place later, which is left alone.
+    ino: u64,
+}
 
-    fn cause(&self) -> Option<&Error> {
-        match *self {
-            DireError::Hyper(ref e) => e.cause(),
-            _ => None,
-        }
-    }
-}
+#[cfg(unix)]
+impl SocketFile {
+    fn new(path: &Path) -> Result<Self, DireError> {
+        let ino = fs::symlink_metadata(path).map_err(DireError::Bind)?.ino();
 
-impl From<hyper::Error> for DireError {
-    fn from(err: hyper::Error) -> DireError {
-        DireError::Hyper(err)
+        Ok(Self {
+            path: path.to_path_buf(),
+            ino,
+        })
     }
 }
 
-impl From<&'static str> for DireError {
-    fn from(err: &str) -> DireError {
-        DireError::Other(err.to_string())
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Ok(metadata) = fs::symlink_metadata(&self.path) {
+            if metadata.ino() == self.ino {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
     }
 }
 
-impl From<String> for DireError {
-    fn from(err: String) -> DireError {
-        DireError::Other(err)
+impl Listener {
+    fn tcp(listener: TcpListener) -> Result<Self, DireError> {
+        let addr = listener.local_addr().map_err(DireError::Bind)?;
+
+        Ok(Listener::Tcp(listener, addr))
     }
-}
 
-/// All middleware must implement this trait.
-///
-/// # Examples
-///
-/// ```rust
-/// # use direkuta::prelude::{Middle, Request, Response};
-/// struct Logger {}
-///
-/// impl Logger {
-///     pub fn new() -> Self {
-///         Self { }
-///     }
-/// }
-///
-/// impl Middle for Logger {
-///     fn run(&self, req: &mut Request) {
-///         println!("[{}] `{}`", req.method(), req.uri());
-///     }
-/// }
-/// ```
-pub trait Middle {
-    /// Called before a request is sent through Router.
-    fn run(&self, req: &mut Request);
-}
+    /// Bind a Unix socket, replacing any existing socket at the path.
+    ///
+    /// With a mode the socket is bound under a temporary name and moved in to place once its
+    /// permissions are set, so it can't be connected to with the default ones.
+    #[cfg(unix)]
+    fn bind_unix(path: &Path, mode: Option<u32>) -> Result<Self, DireError> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(DireError::Bind(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("`{}` exists and is not a socket", path.display()),
+                )));
+            }
 
-/// A simple logger middleware.
-///
-/// # Examples
-///
-/// ```rust
-/// # use direkuta::prelude::*;
-/// Direkuta::new()
-///     .middle(Logger::new());
-/// ```
-pub struct Logger {}
+            fs::remove_file(path).map_err(DireError::Bind)?;
+        }
 
-impl Logger {
-    /// Constructs a new Logger.
-    pub fn new() -> Self {
-        Logger::default()
-    }
-}
+        let mode = match mode {
+            Some(mode) => mode,
+            None => {
+                let listener = UnixListener::bind(path).map_err(DireError::Bind)?;
 
-impl Middle for Logger {
-    #[inline]
-    fn run(&self, req: &mut Request) {
-        println!("[{:>6}] `{}`", req.method().as_ref(), req.uri());
+                return Ok(Listener::Unix(listener, Some(SocketFile::new(path)?)));
+            }
+        };
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(format!(".{}.tmp", process::id()));
+        let temp = PathBuf::from(temp);
+
+        let listener = UnixListener::bind(&temp).map_err(DireError::Bind)?;
+
+        let placed = fs::set_permissions(&temp, Permissions::from_mode(mode))
+            .and_then(|()| fs::rename(&temp, path));
+
+        if let Err(e) = placed {
+            let _ = fs::remove_file(&temp);
+            return Err(DireError::Bind(e));
+        }
+
+        Ok(Listener::Unix(listener, Some(SocketFile::new(path)?)))
     }
-}
 
-impl Default for Logger {
-    fn default() -> Logger {
-        Logger {}
+    /// Serve connections from the listener.
+    fn serve<F>(
+        self,
+        handle: &Handle,
+        app: Direkuta,
+        signal: Shared<F>,
+    ) -> Result<Box<dyn Future<Item = (), Error = DireError> + Send + 'static>, DireError>
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        match self {
+            Listener::Tcp(listener, _) => {
+                let listener =
+                    TokioTcpListener::from_std(listener, handle).map_err(DireError::Bind)?;
+
+                Ok(serve(Incoming::new(listener, &app.config), app, signal))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, file) => {
+                // Dropping the file removes it, nothing answers on it once this returns
+                let listener = TokioUnixListener::from_std(listener, handle).map_err(DireError::Bind)?;
+                let served = serve(Incoming::new(listener, &app.config), app, signal);
+
+                Ok(Box::new(served.then(move |result| {
+                    drop(file);
+                    result
+                })))
+            }
+        }
     }
-}
 
-/// A wrapper around IndexMap<TypeId, Any>, used to store server state.
-///
-/// Stored state cannot be dynamically created and must be static.
-pub struct State {
-    inner: IndexMap<TypeId, Box<Any + Send + Sync + 'static>>,
+    /// Describe where the listener can be reached.
+    fn url(&self, scheme: &str) -> String {
+        match *self {
+            Listener::Tcp(_, addr) => format!("{}://{}", scheme, addr),
+            #[cfg(unix)]
+            Listener::Unix(ref listener, ref path) => {
+                // A socket bound under a temporary name reports that name as its address.
+                let path = match path {
+                    Some(file) => Some(file.path.display().to_string()),
+                    None => listener
+                        .local_addr()
+                        .ok()
+                        .and_then(|addr| addr.as_pathname().map(|path| path.display().to_string())),
+                };
+
+                format!("{}+unix://{}", scheme, path.unwrap_or_default())
+            }
+        }
+    }
 }
 
-impl State {
-    /// Constructs a new State.
+impl Bound {
+    /// Also bind the server to another address.
     ///
     /// # Examples
     ///
     /// ```rust
     /// # use direkuta::prelude::*;
-    /// let state = State::new();
+    /// let bound = Direkuta::new()
+    ///     .bind("127.0.0.1:0")
+    ///     .unwrap()
+    ///     .bind("127.0.0.1:0")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(bound.local_addrs().len(), 2);
     /// ```
-    pub fn new() -> Self {
-        State::default()
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound.
+    pub fn bind(self, addr: impl ToSocketAddrs) -> Result<Self, DireError> {
+        let listener = TcpListener::bind(addr).map_err(DireError::Bind)?;
+
+        self.listen(listener)
     }
 
-    /// Sets the value of whatever type is passed.
+    /// Also use an already bound listener for the server.
     ///
-    /// Please note that you cannot have two states of the same types, one will overwrite the other.
+    /// # Errors
     ///
-    /// # Examples
+    /// Returns an error if the listener has no local address.
+    pub fn listen(mut self, listener: TcpListener) -> Result<Self, DireError> {
+        self.listeners.push(Listener::tcp(listener)?);
+        Ok(self)
+    }
+
+    /// Also bind the server to a Unix socket path, see `Direkuta::bind_unix`.
     ///
-    /// ```rust
-    /// # use direkuta::State;
-    /// let mut state = State::new();
+    /// # Errors
     ///
-    /// state.set(String::from("Hello World!"));
-    /// ```
-    pub fn set<T: Any + Send + Sync + 'static>(&mut self, ctx: T) {
-        let _ = self.inner.insert(TypeId::of::<T>(), Box::new(ctx));
+    /// Returns an error if the socket cannot be bound.
+    #[cfg(unix)]
+    pub fn bind_unix(mut self, path: impl AsRef<Path>) -> Result<Self, DireError> {
+        let mode = self.app.config.unix_socket_mode;
+
+        self.listeners.push(Listener::bind_unix(path.as_ref(), mode)?);
+        Ok(self)
     }
 
-    /// Attempt to get a value based on type.
+    /// Also use an already bound Unix listener for the server.
+    #[cfg(unix)]
+    pub fn listen_unix(mut self, listener: UnixListener) -> Self {
+        self.listeners.push(Listener::Unix(listener, None));
+        self
+    }
+
+    /// Also use the sockets passed by systemd socket activation, see `Direkuta::listen_fds`.
     ///
-    /// Use this if you are not sure if the type exists.
+    /// # Errors
+    ///
+    /// Returns an error if no sockets were passed to this process or if `LISTEN_FDS` is not a
+    /// valid count.
+    #[cfg(unix)]
+    pub fn listen_fds(mut self) -> Result<Self, DireError> {
+        let listeners = inherited_listeners()?;
+
+        if listeners.is_empty() {
+            return Err(DireError::Bind(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No sockets were passed in LISTEN_FDS",
+            )));
+        }
+
+        self.listeners.extend(listeners);
+        Ok(self)
+    }
+
+    /// Return the first TCP address the server is bound to, None when it is only bound to Unix sockets.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addrs().into_iter().next()
+    }
+
+    /// Return all TCP addresses the server is bound to.
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|listener| match *listener {
+                Listener::Tcp(_, addr) => Some(addr),
+                #[cfg(unix)]
+                Listener::Unix(..) => None,
+            }).collect()
+    }
+
+    /// Run server as a Hyper server.
     ///
     /// # Examples
     ///
-    /// ```rust
+    /// ```rust,ignore
     /// # use direkuta::prelude::*;
-    /// let mut state = State::new();
+    /// // Not tested due to the fact that its a web server.
+    /// Direkuta::new()
+    ///     .bind("0.0.0.0:3000")
+    ///     .unwrap()
+    ///     .run()
+    ///     .unwrap();
+    /// ```
     ///
-    /// state.set(String::from("Hello World!"));
+    /// # Errors
     ///
-    /// match state.try_get::<String>() {
-    ///     Some(s) => {
-    ///         println!("{}", s);
-    ///     },
-    ///     None => {
-    ///         println!("String not found in state");
-    ///     },
-    /// }
-    /// ```
-    pub fn try_get<T: Any + Send + Sync + 'static>(&self) -> Option<&T> {
-        self.inner
-            .get(&TypeId::of::<T>())
-            .and_then(|b| b.downcast_ref::<T>())
+    /// Returns an error if the runtime could not be created or if the listener could not be
+    /// registered with it.
+    ///
+    /// Any errors that come from the running server will be printed to the console.
+    #[inline]
+    pub fn run(self) -> Result<(), DireError> {
+        self.run_until(future::empty())
     }
 
-    /// Attempt to get a value based on type.
-    ///
-    /// Use this if you are not sure if the type exists and want an Result instead of Option.
+    /// Run server as a Hyper server until the given signal resolves.
     ///
-    /// # Examples
+    /// See `Direkuta::run_until` for how shutdown is handled.
     ///
-    /// ```rust
-    /// # use direkuta::prelude::*;
-    /// let mut state = State::new();
+    /// # Errors
     ///
-    /// state.set(String::from("Hello World!"));
+    /// Returns an error if the runtime could not be created or if the listener could not be
+    /// registered with it.
     ///
-    /// match state.get_err::<String>() {
-    ///     Ok(s) => {
-    ///         println!("{}", s);
-    ///     },
-    ///     Err(e) => {
-    ///         println!("String not found in state");
-    ///     },
-    /// }
-    /// ```
-    pub fn get_err<T: Any + Send + Sync + 'static>(&self) -> Result<&T, DireError> {
-        match self.inner
-            .get(&TypeId::of::<T>())
-            .and_then(|b| b.downcast_ref::<T>()) {
-                Some(inner) => Ok(inner),
-                None => Err(DireError::StateNotFound)
-            }
+    /// Any errors that come from the running server will be printed to the console.
+    pub fn run_until(
+        self,
+        signal: impl Future<Item = (), Error = ()> + Send + 'static,
+    ) -> Result<(), DireError> {
+        let workers = self.app.config.workers;
+
+        #[cfg(feature = "tls")]
+        let scheme = if self.app.tls.is_some() { "https" } else { "http" };
+        #[cfg(not(feature = "tls"))]
+        let scheme = "http";
+
+        let urls: Vec<String> = self
+            .listeners
+            .iter()
+            .map(|listener| listener.url(scheme))
+            .collect();
+
+        let server = self
+            .serve_until(signal)?
+            .map_err(|e| eprintln!("server error: {}", e));
+
+        for url in urls {
+            println!("Direkuta listening on {}", url);
+        }
+
+        let mut builder = RuntimeBuilder::new();
+
+        if let Some(workers) = workers {
+            let _ = builder.core_threads(workers);
+        }
+
+        let mut runtime = builder.build().map_err(DireError::Io)?;
+
+        let _ = runtime.block_on(server);
+
+        // Drop any connections that outlived the grace period.
+        let _ = runtime.shutdown_now().wait();
+
+        Ok(())
     }
 
-    /// Get a value based on type.
+    /// Create the server as a future, to be spawned on an existing Tokio runtime.
     ///
-    /// This is a wrapper around try_get.
+    /// The future must be run on a Tokio runtime as connections are spawned onto it.
     ///
     /// # Examples
     ///
     /// ```rust
+    /// # extern crate direkuta;
+    /// # extern crate futures;
+    /// # extern crate tokio;
     /// # use direkuta::prelude::*;
-    /// let mut state = State::new();
+    /// # use futures::Future;
+    /// # fn main() {
+    /// let mut runtime = tokio::runtime::Runtime::new().unwrap();
     ///
-    /// state.set(String::from("Hello World!"));
+    /// let server = Direkuta::new()
+    ///     .bind("127.0.0.1:0")
+    ///     .unwrap()
+    ///     .serve()
+    ///     .unwrap();
     ///
-    /// println!("{}", state.get::<String>());
+    /// runtime.spawn(server.map_err(|e| eprintln!("{}", e)));
+    /// # }
     /// ```
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// If the key does not exist the function will panic.
+    /// Returns an error if the listener could not be registered with the runtime.
+    #[inline]
+    pub fn serve(
+        self,
+    ) -> Result<Box<dyn Future<Item = (), Error = DireError> + Send + 'static>, DireError> {
+        self.serve_until(future::empty())
+    }
+
+    /// Create the server as a future that stops once the given signal resolves.
     ///
-    /// If you do not know if the type exists use `try_get`.
-    pub fn get<T: Any + Send + Sync + 'static>(&self) -> &T {
-        self.try_get::<T>()
-            .unwrap_or_else(|| panic!("Key not found in state: {:?}", &TypeId::of::<T>()))
+    /// Once the signal resolves no new connections are accepted, and the future resolves when
+    /// the active requests are done or the shutdown timeout is over. Connections are spawned onto
+    /// the runtime, so any still open after the timeout keep running until the runtime they were
+    /// spawned on is shut down, only `run_until` drops them as it owns its runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the listener could not be registered with the runtime.
+    pub fn serve_until(
+        self,
+        signal: impl Future<Item = (), Error = ()> + Send + 'static,
+    ) -> Result<Box<dyn Future<Item = (), Error = DireError> + Send + 'static>, DireError> {
+        let Bound { app, listeners } = self;
+        let signal = signal.shared();
+        let handle = Handle::default();
+
+        let servers = listeners
+            .into_iter()
+            .map(|listener| listener.serve(&handle, app.share(), signal.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Box::new(future::join_all(servers).map(|_| ())))
     }
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self {
-            inner: IndexMap::new(),
+/// Serve connections from the listener, over TLS if it is configured.
+fn serve<L, F>(
+    incoming: Incoming<L>,
+    app: Direkuta,
+    signal: Shared<F>,
+) -> Box<dyn Future<Item = (), Error = DireError> + Send + 'static>
+where
+    L: Accept + Send + 'static,
+    F: Future<Item = (), Error = ()> + Send + 'static,
+{
+    #[cfg(feature = "tls")]
+    {
+        if let Some(tls) = app.tls.clone() {
+            let incoming = TlsIncoming::new(incoming, tls, app.config.tls_handshake_timeout);
+
+            return graceful(incoming, app, signal);
         }
     }
+
+    graceful(incoming, app, signal)
 }
 
-/// The current mode of the router path parser.
-enum Mode {
-    /// Currently writing the id of the capture.
-    Id,
-    /// Currently writing the regex of the capture.
-    Regex,
-    /// Looking for another Id or push any other character.
-    Look,
+/// Serve connections until the signal resolves.
+fn graceful<I, F>(
+    incoming: I,
+    app: Direkuta,
+    signal: Shared<F>,
+) -> Box<dyn Future<Item = (), Error = DireError> + Send + 'static>
+where
+    I: Stream + Send + 'static,
+    I::Error: Into<Box<dyn Error + Send + Sync>>,
+    I::Item: Connection + Read + Write + AsyncRead + AsyncWrite + Send + 'static,
+    F: Future<Item = (), Error = ()> + Send + 'static,
+{
+    let grace = app.config.shutdown_timeout;
+
+    let mut builder = Server::builder(incoming.map(SkipContinue::new))
+        .http1_keepalive(app.config.keep_alive);
+
+    if let Some(size) = app.config.max_header_size {
+        builder = builder.http1_max_buf_size(cmp::max(size, MIN_HEADER_SIZE));
+    }
+
+    let server = builder
+        .serve(make_service_fn(move |conn: &SkipContinue<I::Item>| {
+            let service = Direkuta {
+                activity: Some(conn.activity()),
+                ..app.share()
+            };
+
+            #[cfg(feature = "tls")]
+            let service = Direkuta {
+                peer: conn.peer_certificate(),
+                ..service
+            };
+
+            future::ok::<_, DireError>(service)
+        }))
+        .with_graceful_shutdown(signal.clone().map(|_| ()))
+        .map_err(DireError::from);
+
+    // Stop waiting on active connections once the grace period is over.
+    let deadline = signal
+        .then(move |_| Delay::new(Instant::now() + grace))
+        .then(|_| Ok(()));
+
+    Box::new(
+        server
+            .select(deadline)
+            .map(|_| ())
+            .map_err(|(e, _)| e),
+    )
 }
 
-/// A wrapper around IndexMap<String, String>.
-///
-/// Stores the captures for a given request.
-pub struct Capture {
-    inner: IndexMap<String, String>,
+/// Details of an accepted connection that are passed on to its requests.
+trait Connection {
+    /// Return the tracker for requests on the connection.
+    fn activity(&self) -> Arc<Activity>;
+
+    /// Return the verified client certificate of the connection.
+    #[cfg(feature = "tls")]
+    fn peer_certificate(&self) -> Option<Arc<PeerCertificate>> {
+        None
+    }
 }
 
-impl Capture {
-    /// Constructs a new Capture.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # use direkuta::prelude::*;
-    /// let capture = Capture::new();
-    /// ```
-    pub fn new() -> Self {
-        Capture::default()
+/// The smallest buffer Hyper accepts for reading request headers.
+const MIN_HEADER_SIZE: usize = 8192;
+
+/// The interim response Hyper queues as soon as it reads a request head with `Expect: 100-continue`.
+const CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+/// Tracks the requests on a connection, so its IO knows when the server is waiting on the client.
+#[derive(Default)]
+struct Activity {
+    /// Requests whose headers have been read.
+    started: AtomicUsize,
+    /// Requests whose handler has finished.
+    finished: AtomicUsize,
+    /// Set when a request body is refused before it is read, so the client is not asked for it.
+    skip_continue: AtomicBool,
+}
+
+impl Activity {
+    /// Return the number of started and finished requests.
+    fn load(&self) -> (usize, usize) {
+        (
+            self.started.load(Ordering::SeqCst),
+            self.finished.load(Ordering::SeqCst),
+        )
     }
+}
 
-    /// Sets the value of whatever key is passed.
+/// Marks a request handler as running until it is dropped.
+struct ActiveRequest(Arc<Activity>);
+
+impl ActiveRequest {
+    fn new(activity: Arc<Activity>) -> Self {
+        let _ = activity.started.fetch_add(1, Ordering::SeqCst);
+
+        ActiveRequest(activity)
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        let _ = self.0.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Closes connections that sit idle or are too slow to send their request headers.
+struct Timed<IO> {
+    io: IO,
+    activity: Arc<Activity>,
+    /// The request counts as of the last check.
+    seen: (usize, usize),
+    /// Reset whenever there is progress on the connection.
+    keep_alive: Option<(Duration, Delay)>,
+    header_read_timeout: Option<Duration>,
+    /// Runs while request headers are read.
+    header_timer: Option<Delay>,
+}
+
+impl<IO> Timed<IO> {
+    fn new(io: IO, config: &Config) -> Self {
+        let now = Instant::now();
+
+        Self {
+            io,
+            activity: Arc::new(Activity::default()),
+            seen: (0, 0),
+            keep_alive: config
+                .keep_alive_timeout
+                .map(|timeout| (timeout, Delay::new(now + timeout))),
+            header_read_timeout: config.header_read_timeout,
+            // The client has to start sending a request right away.
+            header_timer: config
+                .header_read_timeout
+                .map(|timeout| Delay::new(now + timeout)),
+        }
+    }
+
+    /// Fail with `TimedOut` once the client has taken too long.
+    fn check(&mut self) -> io::Result<()> {
+        let (started, finished) = self.activity.load();
+
+        if (started, finished) != self.seen {
+            // The headers of a new request were read.
+            if started != self.seen.0 {
+                self.header_timer = None;
+            }
+
+            self.seen = (started, finished);
+            self.progress(false);
+        }
+
+        if let Some(ref mut timer) = self.header_timer {
+            if expired(timer) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Request headers took too long",
+                ));
+            }
+        }
+
+        // A running handler is not the client's fault.
+        if started == finished {
+            if let Some((_, ref mut timer)) = self.keep_alive {
+                if expired(timer) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "Connection was idle for too long",
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Note that there was progress on the connection.
+    fn progress(&mut self, read: bool) {
+        let now = Instant::now();
+
+        if let Some((timeout, ref mut timer)) = self.keep_alive {
+            timer.reset(now + timeout);
+        }
+
+        // Data between requests is the start of the next request's headers.
+        if read && self.header_timer.is_none() {
+            let (started, finished) = self.activity.load();
+
+            if started == finished {
+                self.header_timer = self
+                    .header_read_timeout
+                    .map(|timeout| Delay::new(now + timeout));
+            }
+        }
+    }
+}
+
+/// Check if a timer has fired, registering the current task to be woken when it does.
+fn expired(timer: &mut Delay) -> bool {
+    match timer.poll() {
+        Ok(Async::Ready(())) => true,
+        // Without a timer there is nothing to enforce.
+        Ok(Async::NotReady) | Err(_) => false,
+    }
+}
+
+impl<IO> Connection for Timed<IO> {
+    fn activity(&self) -> Arc<Activity> {
+        self.activity.clone()
+    }
+}
+
+impl<IO: Read> Read for Timed<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check()?;
+
+        let n = self.io.read(buf)?;
+
+        if n > 0 {
+            self.progress(true);
+        }
+
+        Ok(n)
+    }
+}
+
+impl<IO: Write> Write for Timed<IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+
+        let n = self.io.write(buf)?;
+
+        if n > 0 {
+            self.progress(false);
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<IO: AsyncRead> AsyncRead for Timed<IO> {}
+
+impl<IO: AsyncWrite> AsyncWrite for Timed<IO> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// Keeps Hyper from asking for a request body that was refused from its headers alone.
+///
+/// Wraps the connection after TLS, so it sees the response as Hyper wrote it.
+struct SkipContinue<IO> {
+    io: IO,
+    activity: Arc<Activity>,
+}
+
+impl<IO: Connection> SkipContinue<IO> {
+    fn new(io: IO) -> Self {
+        let activity = io.activity();
+
+        Self { io, activity }
+    }
+}
+
+impl<IO: Connection> Connection for SkipContinue<IO> {
+    fn activity(&self) -> Arc<Activity> {
+        self.activity.clone()
+    }
+
+    #[cfg(feature = "tls")]
+    fn peer_certificate(&self) -> Option<Arc<PeerCertificate>> {
+        self.io.peer_certificate()
+    }
+}
+
+impl<IO: Read> Read for SkipContinue<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<IO: Write> Write for SkipContinue<IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The service runs before Hyper flushes, so the flag is set by the time it is written.
+        if !self.activity.skip_continue.load(Ordering::SeqCst) {
+            return self.io.write(buf);
+        }
+
+        // Hyper doesn't queue the interim response for every request, like HTTP/1.0 ones
+        if !buf.starts_with(CONTINUE) {
+            let n = self.io.write(buf)?;
+            self.activity.skip_continue.store(false, Ordering::SeqCst);
+
+            return Ok(n);
+        }
+
+        let rest = &buf[CONTINUE.len()..];
+        let n = if rest.is_empty() { 0 } else { self.io.write(rest)? };
+
+        // Hyper moves past the prefix only now, after an error it writes the same bytes again
+        self.activity.skip_continue.store(false, Ordering::SeqCst);
+
+        Ok(CONTINUE.len() + n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<IO: AsyncRead> AsyncRead for SkipContinue<IO> {}
+
+impl<IO: AsyncWrite> AsyncWrite for SkipContinue<IO> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+/// A listener that connections can be accepted from.
+trait Accept {
+    /// The accepted connection.
+    type Io: Read + Write + AsyncRead + AsyncWrite + Send + 'static;
+
+    /// Accept a new connection.
+    fn accept(&mut self) -> Poll<Self::Io, io::Error>;
+}
+
+impl Accept for TokioTcpListener {
+    type Io = TokioTcpStream;
+
+    fn accept(&mut self) -> Poll<Self::Io, io::Error> {
+        let (stream, _) = try_ready!(self.poll_accept());
+        Ok(Async::Ready(stream))
+    }
+}
+
+#[cfg(unix)]
+impl Accept for TokioUnixListener {
+    type Io = TokioUnixStream;
+
+    fn accept(&mut self) -> Poll<Self::Io, io::Error> {
+        let (stream, _) = try_ready!(self.poll_accept());
+        Ok(Async::Ready(stream))
+    }
+}
+
+/// Accepts connections, backing off after errors instead of stopping the server.
+struct Incoming<L> {
+    listener: L,
+    config: Arc<Config>,
+    /// Set after an accept error, to back off instead of spinning.
+    backoff: Option<Delay>,
+}
+
+impl<L> Incoming<L> {
+    fn new(listener: L, config: &Arc<Config>) -> Self {
+        Self {
+            listener,
+            config: config.clone(),
+            backoff: None,
+        }
+    }
+}
+
+impl<L: Accept> Stream for Incoming<L> {
+    type Item = Timed<L::Io>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(ref mut backoff) = self.backoff {
+                if let Ok(Async::NotReady) = backoff.poll() {
+                    return Ok(Async::NotReady);
+                }
+            }
+
+            self.backoff = None;
+
+            match self.listener.accept() {
+                Ok(Async::Ready(io)) => return Ok(Async::Ready(Some(Timed::new(io, &self.config)))),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                // The client went away before it was accepted, that only affects its own connection.
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::ConnectionAborted
+                        || e.kind() == io::ErrorKind::ConnectionReset => {}
+                Err(e) => {
+                    // Most likely out of file descriptors, wait for some to free up.
+                    eprintln!("accept error: {}", e);
+
+                    self.backoff = Some(Delay::new(Instant::now() + Duration::from_secs(1)));
+                }
+            }
+        }
+    }
+}
+
+/// Turn a would block error into `NotReady`.
+#[cfg(feature = "tls")]
+fn nonblocking<T>(result: io::Result<T>) -> Poll<T, io::Error> {
+    match result {
+        Ok(t) => Ok(Async::Ready(t)),
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+        Err(e) => Err(e),
+    }
+}
+
+/// Accepts connections and completes their TLS handshakes before handing them to Hyper.
+#[cfg(feature = "tls")]
+struct TlsIncoming<L: Accept> {
+    incoming: Incoming<L>,
+    config: Arc<ServerConfig>,
+    /// How long a client has to complete its handshake.
+    timeout: Duration,
+    handshakes: FuturesUnordered<Handshake<Timed<L::Io>>>,
+}
+
+#[cfg(feature = "tls")]
+impl<L: Accept> TlsIncoming<L> {
+    fn new(incoming: Incoming<L>, config: Arc<ServerConfig>, timeout: Duration) -> Self {
+        Self {
+            incoming,
+            config,
+            timeout,
+            handshakes: FuturesUnordered::new(),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<L: Accept> Stream for TlsIncoming<L> {
+    type Item = TlsStream<Timed<L::Io>>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        while let Async::Ready(Some(io)) = self.incoming.poll()? {
+            self.handshakes.push(Handshake {
+                stream: Some(TlsStream::new(io, ServerSession::new(&self.config))),
+                deadline: Delay::new(Instant::now() + self.timeout),
+            });
+        }
+
+        loop {
+            match self.handshakes.poll() {
+                Ok(Async::Ready(Some(stream))) => return Ok(Async::Ready(Some(stream))),
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => return Ok(Async::NotReady),
+                // A failed handshake only affects its own connection.
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Completes a server TLS handshake.
+#[cfg(feature = "tls")]
+struct Handshake<IO> {
+    stream: Option<TlsStream<IO>>,
+    /// Clients that never finish the handshake would otherwise hold their connection forever.
+    deadline: Delay,
+}
+
+#[cfg(feature = "tls")]
+impl<IO: Read + Write> Future for Handshake<IO> {
+    type Item = TlsStream<IO>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if expired(&mut self.deadline) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "TLS handshake took too long"));
+        }
+
+        {
+            let stream = self.stream.as_mut().expect("Handshake polled after completion");
+
+            while stream.session.is_handshaking() {
+                try_ready!(nonblocking(stream.flush_tls()));
+
+                if !stream.session.is_handshaking() {
+                    break;
+                }
+
+                if !try_ready!(nonblocking(stream.read_tls())) {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+
+            try_ready!(nonblocking(stream.flush_tls()));
+        }
+
+        Ok(Async::Ready(self.stream.take().expect("Handshake polled after completion")))
+    }
+}
+
+/// A server TLS connection over any IO.
+#[cfg(feature = "tls")]
+struct TlsStream<IO> {
+    io: IO,
+    session: ServerSession,
+    eof: bool,
+    closing: bool,
+}
+
+#[cfg(feature = "tls")]
+impl<IO: Read + Write> TlsStream<IO> {
+    fn new(io: IO, session: ServerSession) -> Self {
+        Self {
+            io,
+            session,
+            eof: false,
+            closing: false,
+        }
+    }
+
+    /// Write out all buffered TLS data.
+    fn flush_tls(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            let _ = self.session.write_tls(&mut self.io)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read and process more TLS data, returns false at the end of the stream.
+    fn read_tls(&mut self) -> io::Result<bool> {
+        if self.session.read_tls(&mut self.io)? == 0 {
+            return Ok(false);
+        }
+
+        match self.session.process_new_packets() {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                // Try to let the peer know what went wrong.
+                let _ = self.flush_tls();
+                Err(io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<IO: Read + Write> Read for TlsStream<IO> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.session.read(buf)? {
+                0 if !self.eof => {}
+                n => return Ok(n),
+            }
+
+            // Messages like alerts may need to go out, they are retried on the next write.
+            match self.flush_tls() {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => result?,
+            }
+
+            self.eof = !self.read_tls()?;
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<IO: Read + Write> Write for TlsStream<IO> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let n = self.session.write(buf)?;
+
+            if n > 0 || buf.is_empty() {
+                // Send what can be sent now, the rest goes out on flush.
+                match self.flush_tls() {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    result => result?,
+                }
+
+                return Ok(n);
+            }
+
+            // The session buffer is full, so it has to be drained first.
+            let _ = self.session.write_tls(&mut self.io)?;
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.session.flush()?;
+        self.flush_tls()?;
+        self.io.flush()
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<IO: Connection> Connection for TlsStream<IO> {
+    fn activity(&self) -> Arc<Activity> {
+        self.io.activity()
+    }
+
+    fn peer_certificate(&self) -> Option<Arc<PeerCertificate>> {
+        let cert = self.session.get_peer_certificates()?.into_iter().next()?;
+
+        PeerCertificate::from_der(cert.0).ok().map(Arc::new)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl<IO: AsyncRead + AsyncWrite> AsyncRead for TlsStream<IO> {}
+
+#[cfg(feature = "tls")]
+impl<IO: AsyncRead + AsyncWrite> AsyncWrite for TlsStream<IO> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        if !self.closing {
+            self.session.send_close_notify();
+            self.closing = true;
+        }
+
+        try_ready!(nonblocking(self.flush_tls()));
+
+        self.io.shutdown()
+    }
+}
+
+impl NewService for Direkuta {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = DireError;
+    type InitError = DireError;
+    type Service = Direkuta;
+    type Future = Box<dyn Future<Item = Self::Service, Error = Self::InitError> + Send>;
+
+    fn new_service(&self) -> Self::Future {
+        Box::new(future::ok(self.share()))
+    }
+}
+
+impl Service for Direkuta {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = DireError;
+    type Future = Box<dyn Future<Item = response::Response<Self::ResBody>, Error = Self::Error> + Send>;
+
+    fn call(&mut self, req: request::Request<Self::ReqBody>) -> Self::Future {
+        let path = req.uri().path().to_owned();
+        let (parts, body) = req.into_parts();
+        let req = Request::new(body, parts);
+
+        let method = req.method().clone();
+        let mut slots = Vec::new();
+
+        let res = catch_panics(&method, &path, || self.respond(req, &path, &mut slots));
+
+        let handle_error = self.handle_error(&method, &path);
+        let res = res.or_else(move |e| Ok(handle_error(e)));
+
+        let middle = self.middle.clone();
+        let res = catch_panics(&method, &path, move || {
+            Box::new(res.map(move |mut res| {
+                for (_, after) in middle.iter() {
+                    after.after(&mut res);
+                }
+
+                res
+            }))
+        });
+
+        let handle_error = self.handle_error(&method, &path);
+        let res = res.or_else(move |e| Ok(handle_error(e)));
+
+        let res: Self::Future = if method == Method::HEAD {
+            Box::new(res.map(without_body))
+        } else {
+            Box::new(res)
+        };
+
+        let active = self.activity.clone().map(ActiveRequest::new);
+
+        Box::new(res.then(move |res| {
+            drop(slots);
+
+            // The connection is not idle until a streamed body has been sent.
+            res.map(|res| match active {
+                Some(active) if res.body().content_length().is_none() => {
+                    res.map(|body| {
+                        Body::wrap_stream(ActiveBody {
+                            body,
+                            active: Some(active),
+                        })
+                    })
+                }
+                _ => res,
+            })
+        }))
+    }
+}
+
+/// A response body that keeps its request active until it has been sent.
+struct ActiveBody {
+    body: Body,
+    /// Dropped once the body ends, or with the body if the client goes away first.
+    active: Option<ActiveRequest>,
+}
+
+impl Stream for ActiveBody {
+    type Item = Chunk;
+    type Error = DireError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let chunk = try_ready!(self.body.poll());
+
+        if chunk.is_none() {
+            drop(self.active.take());
+        }
+
+        Ok(Async::Ready(chunk))
+    }
+}
+
+impl Direkuta {
+    /// Return a closure turning errors in to responses, marking those of handlers that timed out
+    /// and telling overloaded clients when to retry.
+    ///
+    /// A panic in the error handler is answered with a bare 500.
+    fn handle_error(
+        &self,
+        method: &Method,
+        path: &str,
+    ) -> impl Fn(DireError) -> response::Response<Body> + Send + 'static {
+        let handler = self.error_handler.clone();
+        let config = self.config.clone();
+        let (method, path) = (method.clone(), path.to_owned());
+
+        move |err| {
+            let timeout = match err {
+                DireError::Timeout(timeout) => Some(timeout),
+                _ => None,
+            };
+
+            let retry_after = match err {
+                DireError::Overloaded(retry_after) => Some(retry_after),
+                _ => None,
+            };
+
+            let mut res = match handler {
+                Some(ref handler) => match panic::catch_unwind(AssertUnwindSafe(|| handler(err))) {
+                    Ok(res) => res,
+                    Err(payload) => {
+                        let _ = panicked(&method, &path, payload);
+
+                        Response::new().with_status(500)
+                    }
+                },
+                None => default_error_handler(err, &config),
+            }.into_hyper();
+
+            if let Some(timeout) = timeout {
+                let _ = res.extensions_mut().insert(TimedOut { timeout });
+            }
+
+            if let Some(retry_after) = retry_after {
+                if !res.headers().contains_key(header::RETRY_AFTER) {
+                    // Retry-After is in whole seconds, round up so clients never come back early.
+                    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+                    let _ = res
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+                }
+            }
+
+            res
+        }
+    }
+
+    /// Run the middleware and find the handler for the request.
+    fn respond(
+        &self,
+        mut req: Request,
+        path: &str,
+        slots: &mut Vec<Slot>,
+    ) -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static> {
+        #[cfg(feature = "tls")]
+        {
+            if let Some(peer) = self.peer.clone() {
+                let _ = req.parts.extensions.insert(peer);
+            }
+        }
+
+        for (_, before) in self.middle.iter() {
+            before.run(&mut req);
+        }
+
+        match self.routes.recognize(req.method(), path) {
+            _ if !admit(slots, &self.in_flight, self.config.max_concurrency) => {
+                Box::new(future::err(DireError::Overloaded(self.config.retry_after)))
+            }
+            Ok((route, _)) if !admit(slots, &route.in_flight, route.max_concurrency) => {
+                Box::new(future::err(DireError::Overloaded(self.config.retry_after)))
+            }
+            Ok((route, cap)) => {
+                let res: Box<dyn Future<Item = _, Error = _> + Send + 'static> = match route.max_body_size.or(self.config.max_body_size) {
+                    Some(limit) => match req.limit_body(limit as u64) {
+                        Some(exceeded) => {
+                            let res = (route.handler)(req, self.state.clone(), cap);
+
+                            // Whatever the handler made of a cut off body, the client sent too much.
+                            Box::new(res.then(move |res| {
+                                if exceeded.load(Ordering::SeqCst) {
+                                    Err(DireError::BodyTooLarge(limit as u64))
+                                } else {
+                                    res
+                                }
+                            }))
+                        }
+                        None => {
+                            let expects_continue = req
+                                .headers()
+                                .get(header::EXPECT)
+                                .is_some_and(|expect| expect == "100-continue");
+
+                            if let (true, Some(activity)) = (expects_continue, &self.activity) {
+                                activity.skip_continue.store(true, Ordering::SeqCst);
+                            }
+
+                            Box::new(future::err(DireError::BodyTooLarge(limit as u64)))
+                        }
+                    },
+                    None => (route.handler)(req, self.state.clone(), cap),
+                };
+
+                match route.timeout.or(self.config.request_timeout) {
+                    Some(timeout) => with_timeout(res, timeout),
+                    None => res,
+                }
+            }
+            Err(StatusCode::METHOD_NOT_ALLOWED) => {
+                let allowed = self
+                    .routes
+                    .allowed(path)
+                    .iter()
+                    .map(|method| method.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                // OPTIONS without its own handler only lists the methods
+                let status = if req.method() == Method::OPTIONS { 204 } else { 405 };
+
+                let mut res = Response::new().with_status(status);
+                if let Ok(allowed) = HeaderValue::from_str(&allowed) {
+                    let _ = res.headers_mut().insert(header::ALLOW, allowed);
+                }
+
+                res.build()
+            }
+            Err(code) => Response::new().with_status(code.as_u16()).build(),
+        }
+    }
+}
+
+/// Drop the body of a response to a HEAD request, keeping its length.
+fn without_body(res: response::Response<Body>) -> response::Response<Body> {
+    let (mut parts, body) = res.into_parts();
+
+    if !parts.headers.contains_key(header::CONTENT_LENGTH) {
+        if let Some(length) = body.content_length() {
+            let _ = parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+        }
+    }
+
+    response::Response::from_parts(parts, Body::empty())
+}
+
+/// Run the closure and the future it returns, turning a panic in either in to `DireError::Panic`.
+fn catch_panics(
+    method: &Method,
+    path: &str,
+    f: impl FnOnce() -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static>,
+) -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static> {
+    let res = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(res) => res,
+        Err(payload) => return Box::new(future::err(panicked(method, path, payload))),
+    };
+
+    let (method, path) = (method.clone(), path.to_owned());
+
+    Box::new(AssertUnwindSafe(res).catch_unwind().then(move |res| match res {
+        Ok(res) => res,
+        Err(payload) => Err(panicked(&method, &path, payload)),
+    }))
+}
+
+/// Log a panic along with the request it happened in.
+fn panicked(method: &Method, path: &str, payload: Box<dyn Any + Send>) -> DireError {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_string(),
+            Err(_) => "Box<Any>".to_string(),
+        },
+    };
+
+    eprintln!("handler panicked on {} `{}`: {}", method, path, message);
+
+    DireError::Panic(message)
+}
+
+/// Answer with the status of the error, only `DireError::Status` messages are shown to the client.
+fn default_error_handler(err: DireError, config: &Config) -> Response {
+    let status = match err {
+        DireError::Timeout(_) => config.timeout_status,
+        ref err => err.status(),
+    };
+
+    match err {
+        DireError::Status(_, message) => Response::new()
+            .with_status(status.as_u16())
+            .with_body(message),
+        err => {
+            // Panics have already been logged with the request they happened in,
+            // and turning requests away is not a failure.
+            if status.is_server_error() && !matches!(err, DireError::Panic(_) | DireError::Overloaded(_)) {
+                eprintln!("handler error: {}", err);
+            }
+
+            Response::new()
+                .with_status(status.as_u16())
+                .with_body(status.canonical_reason().unwrap_or_default())
+        }
+    }
+}
+
+/// Holds one place among the requests allowed to run at once, giving it back when dropped.
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let _ = self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Take a place for the request if the limit allows it, there is always room without a limit.
+fn admit(slots: &mut Vec<Slot>, count: &Arc<AtomicUsize>, limit: Option<usize>) -> bool {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return true,
+    };
+
+    // Dropping the slot gives the place back when the limit has been reached.
+    let slot = Slot(count.clone());
+
+    if count.fetch_add(1, Ordering::SeqCst) < limit {
+        slots.push(slot);
+        true
+    } else {
+        false
+    }
+}
+
+/// Limit how long a handler may run, failing with `DireError::Timeout` once it is over.
+fn with_timeout(
+    res: Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static>,
+    timeout: Duration,
+) -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static> {
+    Box::new(Timeout::new(res, timeout).then(move |result| match result {
+        Ok(res) => Ok(res),
+        Err(ref e) if e.is_elapsed() => Err(DireError::Timeout(timeout)),
+        Err(e) => Err(e
+            .into_inner()
+            .unwrap_or_else(|| DireError::Other("Timer error".to_string()))),
+    }))
+}
+
+/// Marks a response that was sent because its handler ran longer than its timeout.
+///
+/// Middleware can look for it in the response extensions.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate direkuta;
+/// # extern crate hyper;
+/// # use direkuta::prelude::*;
+/// # use hyper::{Body, Response};
+/// struct TimeoutLogger;
+///
+/// impl Middle for TimeoutLogger {
+///     fn run(&self, _: &mut Request) {}
+///
+///     fn after(&self, res: &mut Response<Body>) {
+///         if let Some(timed_out) = res.extensions().get::<TimedOut>() {
+///             eprintln!("Handler timed out after {:?}", timed_out.timeout());
+///         }
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TimedOut {
+    timeout: Duration,
+}
+
+impl TimedOut {
+    /// Return the timeout the handler ran over.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Internal configuration for the server.
+///
+/// Allows finer control of preset variables like template and static path.
+pub struct Config {
+    template_path: String,
+    static_path: String,
+    shutdown_timeout: Duration,
+    workers: Option<usize>,
+    keep_alive: bool,
+    keep_alive_timeout: Option<Duration>,
+    header_read_timeout: Option<Duration>,
+    max_header_size: Option<usize>,
+    request_timeout: Option<Duration>,
+    timeout_status: StatusCode,
+    max_body_size: Option<usize>,
+    max_concurrency: Option<usize>,
+    retry_after: Duration,
+    #[cfg(unix)]
+    unix_socket_mode: Option<u32>,
+    #[cfg(feature = "tls")]
+    tls_certificate: Option<String>,
+    #[cfg(feature = "tls")]
+    tls_key: Option<String>,
+    #[cfg(feature = "tls")]
+    tls_client_ca: Option<String>,
+    #[cfg(feature = "tls")]
+    tls_client_optional: bool,
+    #[cfg(feature = "tls")]
+    tls_handshake_timeout: Duration,
+}
+
+impl Config {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the path for templates, defaults to "templates".
+    #[inline]
+    pub fn template_path(&mut self, path: impl Into<String>) {
+        self.template_path = path.into();
+    }
+
+    /// Set the path for static files, defaults to "static".
+    #[inline]
+    pub fn static_path(&mut self, path: impl Into<String>) {
+        self.static_path = path.into();
+    }
+
+    /// Set how long active requests are given to finish during shutdown, defaults to 30 seconds.
+    #[inline]
+    pub fn shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    /// Set the number of worker threads used by `run`, defaults to the number of CPU cores.
+    ///
+    /// This has no effect when the server is spawned onto an existing runtime with `serve`.
+    /// A count of 0 is reported as an error when the server is built.
+    #[inline]
+    pub fn workers(&mut self, count: usize) {
+        self.workers = Some(count);
+    }
+
+    /// Set whether connections are kept open between requests, defaults to true.
+    #[inline]
+    pub fn keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    /// Set how long a connection may sit idle before it is closed, defaults to 60 seconds.
+    ///
+    /// A connection is idle while it waits for the next request, or while the client stops
+    /// reading a response. Handlers and streamed response bodies do not count as idle time.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// Direkuta::config(|c| {
+    ///     c.keep_alive_timeout(Duration::from_secs(5));
+    ///     c.header_read_timeout(Duration::from_secs(10));
+    ///     c.max_header_size(16 * 1024);
+    /// });
+    /// ```
+    #[inline]
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) {
+        self.keep_alive_timeout = Some(timeout);
+    }
+
+    /// Set how long a client has to send a request's headers, defaults to 30 seconds.
+    ///
+    /// For a new connection this includes the TLS handshake.
+    #[inline]
+    pub fn header_read_timeout(&mut self, timeout: Duration) {
+        self.header_read_timeout = Some(timeout);
+    }
+
+    /// Set the largest request head in bytes, defaults to around 400KB.
+    ///
+    /// Larger requests are answered with `431 Request Header Fields Too Large`.
+    /// Sizes below 8KB are raised to 8KB.
+    #[inline]
+    pub fn max_header_size(&mut self, size: usize) {
+        self.max_header_size = Some(size);
+    }
+
+    /// Set how long a handler may run before it is dropped, by default there is no limit.
+    ///
+    /// Routes can override this with `RouteBuilder::timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// use direkuta::prelude::hyper::StatusCode;
+    /// use std::time::Duration;
+    ///
+    /// Direkuta::config(|c| {
+    ///     c.request_timeout(Duration::from_secs(10));
+    ///     c.timeout_status(StatusCode::SERVICE_UNAVAILABLE);
+    /// });
+    /// ```
+    #[inline]
+    pub fn request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = Some(timeout);
+    }
+
+    /// Set the status the default error handler sends when a handler times out, defaults to 504.
+    #[inline]
+    pub fn timeout_status(&mut self, status: StatusCode) {
+        self.timeout_status = status;
+    }
+
+    /// Set the largest request body in bytes, by default there is no limit.
+    ///
+    /// Requests with a larger `Content-Length` get a `413 Payload Too Large` without calling the
+    /// handler, so clients waiting on `Expect: 100-continue` never send the body.
+    /// Bodies without a length are cut off once they pass the limit, and also get a 413.
+    /// Both end with a `DireError::BodyTooLarge`, so the error handler can render the response.
+    ///
+    /// Routes can override this with `RouteBuilder::max_body_size`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::config(|c| {
+    ///     c.max_body_size(1024 * 1024);
+    /// });
+    /// ```
+    #[inline]
+    pub fn max_body_size(&mut self, size: usize) {
+        self.max_body_size = Some(size);
+    }
+
+    /// Set how many requests may be handled at once, by default there is no limit.
+    ///
+    /// Requests past the limit end straight away with a `DireError::Overloaded`, instead of waiting
+    /// their turn. By default it is answered with `503 Service Unavailable`, and a `Retry-After`
+    /// header is added unless the error handler set one.
+    ///
+    /// Routes can set their own limit with `RouteBuilder::max_concurrency`,
+    /// a request has to fit within both.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// Direkuta::config(|c| {
+    ///     c.max_concurrency(512);
+    ///     c.retry_after(Duration::from_secs(5));
+    /// });
+    /// ```
+    #[inline]
+    pub fn max_concurrency(&mut self, limit: usize) {
+        self.max_concurrency = Some(limit);
+    }
+
+    /// Set the `Retry-After` sent with requests turned away by a concurrency limit, defaults to 1 second.
+    ///
+    /// It is rounded up to whole seconds.
+    #[inline]
+    pub fn retry_after(&mut self, delay: Duration) {
+        self.retry_after = delay;
+    }
+
+    /// Set the permissions of sockets created by `Direkuta::bind_unix`, e.g. `0o660`.
+    ///
+    /// Defaults to the permissions given by the process umask.
+    #[cfg(unix)]
+    #[inline]
+    pub fn unix_socket_mode(&mut self, mode: u32) {
+        self.unix_socket_mode = Some(mode);
+    }
+
+    /// Set the path of the PEM encoded TLS certificate chain, enabling HTTPS.
+    ///
+    /// The private key must also be set with `tls_key`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::config(|c| {
+    ///     c.tls_certificate("tls/cert.pem");
+    ///     c.tls_key("tls/key.pem");
+    /// });
+    /// ```
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_certificate(&mut self, path: impl Into<String>) {
+        self.tls_certificate = Some(path.into());
+    }
+
+    /// Set the path of the PEM encoded TLS private key, in either PKCS#8 or RSA format.
+    ///
+    /// The certificate chain must also be set with `tls_certificate`.
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_key(&mut self, path: impl Into<String>) {
+        self.tls_key = Some(path.into());
+    }
+
+    /// Set the path of a PEM encoded CA bundle, requiring clients to present a certificate signed by it.
+    ///
+    /// The verified certificate is available from `Request::peer_certificate`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::config(|c| {
+    ///     c.tls_certificate("tls/cert.pem");
+    ///     c.tls_key("tls/key.pem");
+    ///     c.tls_client_ca("tls/clients.pem");
+    /// });
+    /// ```
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_client_ca(&mut self, path: impl Into<String>) {
+        self.tls_client_ca = Some(path.into());
+    }
+
+    /// Set whether clients may connect without a certificate, defaults to false.
+    ///
+    /// Certificates that are presented must still be signed by the `tls_client_ca` bundle.
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_client_optional(&mut self, optional: bool) {
+        self.tls_client_optional = optional;
+    }
+
+    /// Set how long a client has to complete the TLS handshake, defaults to 10 seconds.
+    ///
+    /// Connections that are still handshaking after this are closed.
+    #[cfg(feature = "tls")]
+    #[inline]
+    pub fn tls_handshake_timeout(&mut self, timeout: Duration) {
+        self.tls_handshake_timeout = timeout;
+    }
+
+    /// Load the TLS certificate and key, if they are set.
+    #[cfg(feature = "tls")]
+    fn tls_config(&self) -> Result<Option<Arc<ServerConfig>>, DireError> {
+        let (cert_path, key_path) = match (&self.tls_certificate, &self.tls_key) {
+            (None, None) => return Ok(None),
+            (Some(cert), Some(key)) => (cert, key),
+            _ => {
+                return Err(DireError::Tls(
+                    "Both a certificate and a private key are required".to_string(),
+                ))
+            }
+        };
+
+        let certs = read_pem(cert_path, pemfile::certs)?;
+
+        if certs.is_empty() {
+            return Err(DireError::Tls(format!("No certificates found in `{}`", cert_path)));
+        }
+
+        let mut keys = read_pem(key_path, pemfile::pkcs8_private_keys)?;
+
+        if keys.is_empty() {
+            keys = read_pem(key_path, pemfile::rsa_private_keys)?;
+        }
+
+        let key = keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| DireError::Tls(format!("No private key found in `{}`", key_path)))?;
+
+        let verifier = match self.tls_client_ca {
+            None => NoClientAuth::new(),
+            Some(ref ca_path) => {
+                let mut roots = RootCertStore::empty();
+
+                for cert in read_pem(ca_path, pemfile::certs)? {
+                    roots.add(&cert).map_err(|e| {
+                        DireError::Tls(format!("Invalid CA certificate in `{}`: {:?}", ca_path, e))
+                    })?;
+                }
+
+                if roots.is_empty() {
+                    return Err(DireError::Tls(format!("No certificates found in `{}`", ca_path)));
+                }
+
+                if self.tls_client_optional {
+                    AllowAnyAnonymousOrAuthenticatedClient::new(roots)
+                } else {
+                    AllowAnyAuthenticatedClient::new(roots)
+                }
+            }
+        };
+
+        let mut config = ServerConfig::new(verifier);
+
+        config
+            .set_single_cert(certs, key)
+            .map_err(|e| DireError::Tls(e.to_string()))?;
+
+        Ok(Some(Arc::new(config)))
+    }
+}
+
+/// Read all items of a PEM file.
+#[cfg(feature = "tls")]
+fn read_pem<T>(
+    path: &str,
+    parse: fn(&mut dyn BufRead) -> Result<Vec<T>, ()>,
+) -> Result<Vec<T>, DireError> {
+    let file = File::open(path)
+        .map_err(|e| DireError::Tls(format!("Unable to open `{}`: {}", path, e)))?;
+
+    parse(&mut io::BufReader::new(file))
+        .map_err(|_| DireError::Tls(format!("Unable to parse `{}`", path)))
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            template_path: "templates".to_string(),
+            static_path: "static".to_string(),
+            shutdown_timeout: Duration::from_secs(30),
+            workers: None,
+            keep_alive: true,
+            keep_alive_timeout: Some(Duration::from_secs(60)),
+            header_read_timeout: Some(Duration::from_secs(30)),
+            max_header_size: None,
+            request_timeout: None,
+            timeout_status: StatusCode::GATEWAY_TIMEOUT,
+            max_body_size: None,
+            max_concurrency: None,
+            retry_after: Duration::from_secs(1),
+            #[cfg(unix)]
+            unix_socket_mode: None,
+            #[cfg(feature = "tls")]
+            tls_certificate: None,
+            #[cfg(feature = "tls")]
+            tls_key: None,
+            #[cfg(feature = "tls")]
+            tls_client_ca: None,
+            #[cfg(feature = "tls")]
+            tls_client_optional: false,
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Wrapper around common and library error types.
+///
+/// You should not have to create your own error type.
+///
+/// Errors returned by handlers are turned in to responses with the status given by
+/// `DireError::status`, see `Direkuta::error_handler`.
+#[derive(Debug)]
+pub enum DireError {
+    /// The server address could not be parsed.
+    Address(AddrParseError),
+    /// The request is malformed, the message says how.
+    BadRequest(String),
+    /// The server address could not be bound.
+    Bind(io::Error),
+    /// The request body is longer than the limit, in bytes.
+    BodyTooLarge(u64),
+    /// A `Config` setting is invalid, the message says which.
+    Config(String),
+    /// Any error that originates from Hyper.
+    Hyper(hyper::Error),
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// A value could not be turned in to or read from JSON.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// General error, for use when no error type exists.
+    Other(String),
+    /// Too many requests are running, holds how long the client should wait before retrying.
+    Overloaded(Duration),
+    /// A handler or middleware panicked, holds the panic message.
+    Panic(String),
+    /// A route parameter is missing or could not be parsed, holds the parameter name.
+    Param(String, Box<dyn Error + Send + Sync>),
+    /// A route path is not a valid pattern.
+    Pattern(String, regex::Error),
+    /// A route can never be matched or uses an id twice.
+    Route(String, String),
+    /// Several routes are broken, holds the error of each.
+    Routes(Vec<DireError>),
+    /// No type found in State.
+    StateNotFound,
+    /// An error to answer with the given status, the message is sent as the body.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate direkuta;
+    /// # extern crate futures;
+    /// # use direkuta::prelude::*;
+    /// use direkuta::prelude::hyper::StatusCode;
+    /// use futures::future;
+    ///
+    /// Direkuta::new()
+    ///     .route(|r| {
+    ///         r.get("/", |_, _, _| {
+    ///             Box::new(future::err(DireError::Status(
+    ///                 StatusCode::UNAUTHORIZED,
+    ///                 "Sign in first".to_string(),
+    ///             )))
+    ///         });
+    ///     });
+    /// ```
+    Status(StatusCode, String),
+    /// The templates could not be parsed or rendered.
+    #[cfg(feature = "html")]
+    Template(TemplateError),
+    /// Something took longer than it was allowed to.
+    Timeout(Duration),
+    /// The TLS certificate or private key could not be loaded.
+    #[cfg(feature = "tls")]
+    Tls(String),
+    /// The URL of a named route could not be built, the message says why.
+    Url(String),
+}
+
+impl DireError {
+    /// Return the HTTP status this error is answered with.
+    ///
+    /// Malformed requests are client errors, anything else that went wrong is a server error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// assert_eq!(DireError::BodyTooLarge(1024).status().as_u16(), 413);
+    /// assert_eq!(DireError::StateNotFound.status().as_u16(), 500);
+    /// ```
+    pub fn status(&self) -> StatusCode {
+        match *self {
+            DireError::BadRequest(_) | DireError::Param(_, _) => StatusCode::BAD_REQUEST,
+            #[cfg(feature = "json")]
+            DireError::Json(_) => StatusCode::BAD_REQUEST,
+            DireError::BodyTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            DireError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            DireError::Status(status, _) => status,
+            DireError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for DireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DireError::Address(ref e) => write!(f, "(DireError [Address] {})", e),
+            DireError::BadRequest(ref e) => write!(f, "(DireError [BadRequest] {})", e),
+            DireError::Bind(ref e) => write!(f, "(DireError [Bind] {})", e),
+            DireError::BodyTooLarge(l) => write!(f, "(DireError [BodyTooLarge] Longer than {} bytes)", l),
+            DireError::Config(ref e) => write!(f, "(DireError [Config] {})", e),
+            DireError::Hyper(ref e) => write!(f, "(DireError [Hyper] {})", e),
+            DireError::Io(ref e) => write!(f, "(DireError [Io] {})", e),
+            #[cfg(feature = "json")]
+            DireError::Json(ref e) => write!(f, "(DireError [Json] {})", e),
+            DireError::Other(ref e) => write!(f, "(DireError [Other] {})", e),
+            DireError::Overloaded(ref r) => write!(f, "(DireError [Overloaded] Retry after {:?})", r),
+            DireError::Panic(ref e) => write!(f, "(DireError [Panic] {})", e),
+            DireError::Param(ref p, ref e) => write!(f, "(DireError [Param] `{}` {})", p, e),
+            DireError::Pattern(ref p, ref e) => write!(f, "(DireError [Pattern] `{}` {})", p, e),
+            DireError::Route(ref p, ref e) => write!(f, "(DireError [Route] `{}` {})", p, e),
+            DireError::Routes(ref errors) => {
+                write!(f, "(DireError [Routes]")?;
+
+                for e in errors {
+                    write!(f, " {}", e)?;
+                }
+
+                write!(f, ")")
+            }
+            DireError::StateNotFound => write!(f, "(DireError [State] Key Not Found)"),
+            DireError::Status(ref s, ref e) => write!(f, "(DireError [Status] {} {})", s, e),
+            #[cfg(feature = "html")]
+            DireError::Template(ref e) => {
+                write!(f, "(DireError [Template] {}", e)?;
+
+                let mut source = e.source();
+
+                while let Some(e) = source {
+                    write!(f, ": {}", e)?;
+                    source = e.source();
+                }
+
+                write!(f, ")")
+            }
+            DireError::Timeout(ref t) => write!(f, "(DireError [Timeout] After {:?})", t),
+            #[cfg(feature = "tls")]
+            DireError::Tls(ref e) => write!(f, "(DireError [Tls] {})", e),
+            DireError::Url(ref e) => write!(f, "(DireError [Url] {})", e),
+        }
+    }
+}
+
+impl Error for DireError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DireError::Address(ref e) => Some(e),
+            DireError::Bind(ref e) => Some(e),
+            DireError::Hyper(ref e) => Some(e),
+            DireError::Io(ref e) => Some(e),
+            #[cfg(feature = "json")]
+            DireError::Json(ref e) => Some(e),
+            DireError::Param(_, ref e) => Some(e.as_ref()),
+            DireError::Pattern(_, ref e) => Some(e),
+            #[cfg(feature = "html")]
+            DireError::Template(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AddrParseError> for DireError {
+    fn from(err: AddrParseError) -> DireError {
+        DireError::Address(err)
+    }
+}
+
+impl From<hyper::Error> for DireError {
+    fn from(err: hyper::Error) -> DireError {
+        DireError::Hyper(err)
+    }
+}
+
+impl From<io::Error> for DireError {
+    fn from(err: io::Error) -> DireError {
+        DireError::Io(err)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for DireError {
+    fn from(err: serde_json::Error) -> DireError {
+        DireError::Json(err)
+    }
+}
+
+#[cfg(feature = "html")]
+impl From<tera::Error> for DireError {
+    fn from(err: tera::Error) -> DireError {
+        DireError::Template(TemplateError::new(&err))
+    }
+}
+
+/// An error from Tera, with the message of each error in its chain.
+///
+/// Tera errors are not `Sync`, so their chain is copied in to errors that are.
+/// Its `source` is the cause of the Tera error, if it had one.
+#[cfg(feature = "html")]
+#[derive(Debug)]
+pub struct TemplateError {
+    message: String,
+    source: Option<Box<TemplateError>>,
+}
+
+#[cfg(feature = "html")]
+impl TemplateError {
+    fn new(err: &tera::Error) -> Self {
+        let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+
+        // Built from the last cause back, so each error holds the ones after it
+        let source = messages[1..].iter().rev().fold(None, |source, message| {
+            Some(Box::new(TemplateError {
+                message: message.clone(),
+                source,
+            }))
+        });
+
+        TemplateError {
+            message: err.to_string(),
+            source,
+        }
+    }
+}
+
+#[cfg(feature = "html")]
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(feature = "html")]
+impl Error for TemplateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.source {
+            Some(ref e) => Some(e.as_ref()),
+            None => None,
+        }
+    }
+}
+
+impl From<&'static str> for DireError {
+    fn from(err: &str) -> DireError {
+        DireError::Other(err.to_string())
+    }
+}
+
+impl From<String> for DireError {
+    fn from(err: String) -> DireError {
+        DireError::Other(err)
+    }
+}
+
+/// All middleware must implement this trait.
+///
+/// # Examples
+///
+/// ```rust
+/// # use direkuta::prelude::{Middle, Request, Response};
+/// struct Logger {}
+///
+/// impl Logger {
+///     pub fn new() -> Self {
+///         Self { }
+///     }
+/// }
+///
+/// impl Middle for Logger {
+///     fn run(&self, req: &mut Request) {
+///         println!("[{}] `{}`", req.method(), req.uri());
+///     }
+/// }
+/// ```
+pub trait Middle {
+    /// Called before a request is sent through Router.
+    fn run(&self, req: &mut Request);
+
+    /// Called with the response before it is sent, does nothing by default.
+    fn after(&self, _res: &mut response::Response<Body>) {}
+}
+
+/// A simple logger middleware.
+///
+/// # Examples
+///
+/// ```rust
+/// # use direkuta::prelude::*;
+/// Direkuta::new()
+///     .middle(Logger::new());
+/// ```
+#[derive(Default)]
+pub struct Logger {}
+
+impl Logger {
+    /// Constructs a new Logger.
+    pub fn new() -> Self {
+        Logger::default()
+    }
+}
+
+impl Middle for Logger {
+    #[inline]
+    fn run(&self, req: &mut Request) {
+        println!("[{:>6}] `{}`", req.method().as_ref(), req.uri());
+    }
+
+    #[inline]
+    fn after(&self, res: &mut response::Response<Body>) {
+        if let Some(timed_out) = res.extensions().get::<TimedOut>() {
+            println!("[{:>6}] Timed out after {:?}", res.status().as_u16(), timed_out.timeout());
+        }
+    }
+}
+
+/// A wrapper around IndexMap<TypeId, Any>, used to store server state.
+///
+/// Stored state cannot be dynamically created and must be static.
+pub struct State {
+    inner: IndexMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
+}
+
+impl State {
+    /// Constructs a new State.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let state = State::new();
+    /// ```
+    pub fn new() -> Self {
+        State::default()
+    }
+
+    /// Sets the value of whatever type is passed.
+    ///
+    /// Please note that you cannot have two states of the same types, one will overwrite the other.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::State;
+    /// let mut state = State::new();
+    ///
+    /// state.set(String::from("Hello World!"));
+    /// ```
+    pub fn set<T: Any + Send + Sync + 'static>(&mut self, ctx: T) {
+        let _ = self.inner.insert(TypeId::of::<T>(), Box::new(ctx));
+    }
+
+    /// Attempt to get a value based on type.
+    ///
+    /// Use this if you are not sure if the type exists.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut state = State::new();
+    ///
+    /// state.set(String::from("Hello World!"));
+    ///
+    /// match state.try_get::<String>() {
+    ///     Some(s) => {
+    ///         println!("{}", s);
+    ///     },
+    ///     None => {
+    ///         println!("String not found in state");
+    ///     },
+    /// }
+    /// ```
+    pub fn try_get<T: Any + Send + Sync + 'static>(&self) -> Option<&T> {
+        self.inner
+            .get(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_ref::<T>())
+    }
+
+    #[cfg(feature = "html")]
+    fn try_get_mut<T: Any + Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.inner
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_mut::<T>())
+    }
+
+    /// Attempt to get a value based on type.
+    ///
+    /// Use this if you are not sure if the type exists and want an Result instead of Option.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut state = State::new();
+    ///
+    /// state.set(String::from("Hello World!"));
+    ///
+    /// match state.get_err::<String>() {
+    ///     Ok(s) => {
+    ///         println!("{}", s);
+    ///     },
+    ///     Err(e) => {
+    ///         println!("String not found in state");
+    ///     },
+    /// }
+    /// ```
+    pub fn get_err<T: Any + Send + Sync + 'static>(&self) -> Result<&T, DireError> {
+        match self.inner
+            .get(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_ref::<T>()) {
+                Some(inner) => Ok(inner),
+                None => Err(DireError::StateNotFound)
+            }
+    }
+
+    /// Get a value based on type.
+    ///
+    /// This is a wrapper around try_get.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut state = State::new();
+    ///
+    /// state.set(String::from("Hello World!"));
+    ///
+    /// println!("{}", state.get::<String>());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the key does not exist the function will panic.
+    ///
+    /// If you do not know if the type exists use `try_get`.
+    pub fn get<T: Any + Send + Sync + 'static>(&self) -> &T {
+        self.try_get::<T>()
+            .unwrap_or_else(|| panic!("Key not found in state: {:?}", &TypeId::of::<T>()))
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            inner: IndexMap::new(),
+        }
+    }
+}
+
+/// The current mode of the router path parser.
+enum Mode {
+    /// Currently writing the id of the capture.
+    Id,
+    /// Currently writing the regex of the capture.
+    Regex,
+    /// Looking for another Id or push any other character.
+    Look,
+}
+
+/// A wrapper around IndexMap<String, String>.
+///
+/// Stores the captures for a given request.
+pub struct Capture {
+    inner: IndexMap<String, String>,
+    /// The types of parameters declared with a built in type.
+    kinds: IndexMap<String, Kind>,
+}
+
+impl Capture {
+    /// Constructs a new Capture.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let capture = Capture::new();
+    /// ```
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    /// Sets the value of whatever key is passed.
+    ///
+    /// Please note that you cannot have two of the same keys, one will overwrite the other.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut capture = Capture::new();
+    ///
+    /// capture.set("message", "Hello World!");
+    /// ```
+    #[inline]
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let _ = self.inner.insert(key.into(), value.into());
+    }
+
+    /// Set the value of a route parameter, returning false if it does not fit its type.
+    fn insert(&mut self, id: &Id, value: &str) -> bool {
+        if let Some(kind) = id.kind {
+            if kind.value(value).is_none() {
+                return false;
+            }
+
+            let _ = self.kinds.insert(id.name.clone(), kind);
+        }
+
+        self.set(id.name.as_str(), value);
+
+        true
+    }
+
+    /// Get a value parsed according to the type declared in the route, like `<id:int>`.
+    ///
+    /// Parameters without a built in type are given as `CaptureValue::Str`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::new()
+    ///     .route(|r| {
+    ///         r.get("/posts/<id:int>", |_, _, c| {
+    ///             match c.value("id") {
+    ///                 Some(CaptureValue::Int(id)) => {
+    ///                     Response::new().with_body(format!("Post {}", id + 1)).build()
+    ///                 }
+    ///                 _ => Response::new().with_status(404).build(),
+    ///             }
+    ///         });
+    ///     });
+    /// ```
+    pub fn value(&self, key: &str) -> Option<CaptureValue<'_>> {
+        let text = self.try_get(key)?;
+
+        match self.kinds.get(key) {
+            Some(kind) => kind.value(text),
+            None => Some(CaptureValue::Str(text)),
+        }
+    }
+
+    /// Attempt to get a value based on key.
+    ///
+    /// Use this if you are not sure if the key exists.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut capture = Capture::new();
+    ///
+    /// capture.set("message", "Hello World!");
+    ///
+    /// match capture.try_get("message") {
+    ///     Some(s) => {
+    ///         println!("{}", s);
+    ///     },
+    ///     None => {
+    ///         println!("Key not found in capture");
+    ///     },
+    /// }
+    /// ```
+    pub fn try_get(&self, key: impl Into<String>) -> Option<&str> {
+        match self.inner.get(&key.into()) {
+            Some(s) => Some(s.as_str()),
+            None => None,
+        }
+    }
+
+    /// Get a value based on key.
+    ///
+    /// This is a wrapper around try_get.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut capture = Capture::new();
+    ///
+    /// capture.set("message", "Hello World!");
+    ///
+    /// println!("{}", capture.get("message"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the key does not exist the function will panic.
+    ///
+    /// If you do not know if the key exists use `try_get`.
+    pub fn get(&self, key: impl Into<String>) -> &str {
+        let key = key.into();
+        self.try_get(key.as_str())
+            .unwrap_or_else(|| panic!("Key not found in captures: {}", key))
+    }
+
+    /// Attempt to get a value based on key as type.
     ///
-    /// Please note that you cannot have two of the same keys, one will overwrite the other.
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// let mut capture = Capture::new();
+    ///
+    /// capture.set("message", "0");
+    ///
+    /// println!("{:?}", capture.try_get_parse::<u8>("message"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the key does not exist the function will panic.
+    pub fn try_get_parse<T: ::std::str::FromStr>(&self, key: &str) -> Option<T>
+    where
+        T::Err: ::std::fmt::Debug
+    {
+        match self.try_get(key) {
+            Some(s) => {
+                let c = s.parse::<T>()
+                    .unwrap_or_else(|_| panic!("Error parsing key to type: {}", key));
+                Some(c)
+            },
+            None => None,
+        }
+    }
+
+    /// Get a value based on key as type, without panicking.
+    ///
+    /// Handlers can return the error to answer with `400 Bad Request`.
     ///
     /// # Examples
     ///
@@ -673,16 +3171,31 @@ impl Capture {
     /// # use direkuta::prelude::*;
     /// let mut capture = Capture::new();
     ///
-    /// capture.set("message", "Hello World!");
+    /// capture.set("id", "nope");
+    ///
+    /// assert!(capture.parse::<u8>("id").is_err());
+    /// assert!(capture.parse::<u8>("missing").is_err());
     /// ```
-    #[inline]
-    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
-        let _ = self.inner.insert(key.into(), value.into());
+    ///
+    /// # Errors
+    ///
+    /// Returns `DireError::Param` if the key does not exist or the value does not parse.
+    pub fn parse<T: ::std::str::FromStr>(&self, key: &str) -> Result<T, DireError>
+    where
+        T::Err: Error + Send + Sync + 'static
+    {
+        let value = self
+            .try_get(key)
+            .ok_or_else(|| DireError::Param(key.to_string(), "Key not found in captures".into()))?;
+
+        value
+            .parse::<T>()
+            .map_err(|e| DireError::Param(key.to_string(), Box::new(e)))
     }
 
-    /// Attempt to get a value based on key.
+    /// Get a value based on key as type.
     ///
-    /// Use this if you are not sure if the key exists.
+    /// This is a wrapper around try_get_parse.
     ///
     /// # Examples
     ///
@@ -690,132 +3203,637 @@ impl Capture {
     /// # use direkuta::prelude::*;
     /// let mut capture = Capture::new();
     ///
-    /// capture.set("message", "Hello World!");
+    /// capture.set("message", "0");
     ///
-    /// match capture.try_get("message") {
-    ///     Some(s) => {
-    ///         println!("{}", s);
-    ///     },
-    ///     None => {
-    ///         println!("Key not found in capture");
-    ///     },
-    /// }
+    /// println!("{}", capture.get_parse::<u8>("message"));
     /// ```
-    pub fn try_get(&self, key: impl Into<String>) -> Option<&str> {
-        match self.inner.get(&key.into()) {
-            Some(s) => Some(s.as_str()),
-            None => None,
+    ///
+    /// # Panics
+    ///
+    /// If the key does not exist the function will panic.
+    ///
+    /// If you do not know if the key exists use `try_get_parse`.
+    pub fn get_parse<T: ::std::str::FromStr>(&self, key: &str) -> T
+    where
+        T::Err: ::std::fmt::Debug
+    {
+        self.try_get_parse(key)
+            .unwrap_or_else(|| panic!("Key not found in captures: {}", key))
+    }
+}
+
+impl Default for Capture {
+    fn default() -> Capture {
+        Capture {
+            inner: IndexMap::new(),
+            kinds: IndexMap::new(),
+        }
+    }
+}
+
+/// A route parameter parsed according to its declared type, see `Capture::value`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureValue<'a> {
+    /// Declared with `<id:int>`.
+    Int(i64),
+    /// Declared with `<id:slug>`.
+    Slug(&'a str),
+    /// Declared with `<id:uuid>`, as its 128 bit number.
+    Uuid(u128),
+    /// Declared with `<id:path>`, may contain slashes.
+    Path(&'a str),
+    /// Declared with a plain `<id>` or a custom regex.
+    Str(&'a str),
+}
+
+type Handler =
+    dyn Fn(Request, Arc<State>, Capture)
+            -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static>
+        + Send
+        + Sync
+        + 'static;
+
+/// Internal route, stores the handler and path details.
+///
+/// This is not to be used directly, it is only used for Direkuta.route.
+struct Route {
+    handler: Box<Handler>,
+    ids: Vec<Id>,
+    /// Set with `RouteBuilder::name`, used to build its URL.
+    name: Option<String>,
+    path: String,
+    pattern: Regex,
+    timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    max_concurrency: Option<usize>,
+    /// Requests being handled by this route, counted when it has a concurrency limit.
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// A route parameter.
+struct Id {
+    name: String,
+    /// Set when the parameter uses a built in type like `<id:int>`.
+    kind: Option<Kind>,
+    /// The regex the parameter matches, without the group named after it.
+    pattern: String,
+}
+
+/// A built in parameter type, standing in for a regex.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// `<id:int>`, a whole number that fits in an `i64`.
+    Int,
+    /// `<id:slug>`, lowercase words joined by dashes.
+    Slug,
+    /// `<id:uuid>`, in its hyphenated form.
+    Uuid,
+    /// `<id:path>`, the rest of the path including slashes.
+    Path,
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "int" => Some(Kind::Int),
+            "slug" => Some(Kind::Slug),
+            "uuid" => Some(Kind::Uuid),
+            "path" => Some(Kind::Path),
+            _ => None,
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Kind::Int => "-?[0-9]+",
+            Kind::Slug => "[a-z0-9]+(?:-[a-z0-9]+)*",
+            Kind::Uuid => {
+                "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}"
+            }
+            Kind::Path => ".+",
+        }
+    }
+
+    /// Turn matched text in to a value, the pattern has already checked its shape.
+    fn value(self, text: &str) -> Option<CaptureValue<'_>> {
+        match self {
+            Kind::Int => text.parse().ok().map(CaptureValue::Int),
+            Kind::Slug => Some(CaptureValue::Slug(text)),
+            Kind::Uuid => u128::from_str_radix(&text.replace('-', ""), 16)
+                .ok()
+                .map(CaptureValue::Uuid),
+            Kind::Path => Some(CaptureValue::Path(text)),
+        }
+    }
+}
+
+impl Route {
+    /// Match the path against the route's regex, mapping each named group to its id.
+    fn captures(&self, path: &str) -> Option<Capture> {
+        // Get captures.
+        let caps = self.pattern.captures(path)?;
+        let mut captures = Capture::new();
+
+        // Each id has a group named after its place, other groups in the pattern are left alone
+        for (index, id) in self.ids.iter().enumerate() {
+            // The group is missing when it is in a part of the path that didn't match
+            if let Some(value) = caps.name(&group(index)) {
+                if !captures.insert(id, value.as_str()) {
+                    return None;
+                }
+            }
+        }
+
+        self.debug_pattern(&mut captures);
+
+        Some(captures)
+    }
+
+    /// Map the values found while walking the segment tree to their ids.
+    fn capture(&self, values: &[Option<&str>]) -> Capture {
+        let mut captures = Capture::new();
+
+        for (id, value) in self.ids.iter().zip(values) {
+            if let Some(value) = value {
+                let _ = captures.insert(id, value);
+            }
+        }
+
+        self.debug_pattern(&mut captures);
+
+        captures
+    }
+
+    fn debug_pattern(&self, captures: &mut Capture) {
+        if cfg!(debug_assertions) {
+            captures.set("debug_pattern", self.pattern.as_str());
+        }
+    }
+
+    /// The route's pattern with the names taken off its ids,
+    /// routes with the same shape match the same paths unless their types differ.
+    fn shape(&self) -> String {
+        (0..self.ids.len()).fold(self.pattern.as_str().to_string(), |pattern, index| {
+            pattern.replacen(&format!("(?P<{}>", group(index)), "(", 1)
+        })
+    }
+
+    /// Check if this route matches every path a route of the same shape does,
+    /// a parameter without a type takes all values of one with a type.
+    fn covers(&self, other: &Route) -> bool {
+        self.ids
+            .iter()
+            .zip(&other.ids)
+            .all(|(id, other)| id.kind.is_none() || id.kind == other.kind)
+    }
+}
+
+/// The name of the regex group of a route's id, ids can hold characters group names can't.
+fn group(index: usize) -> String {
+    format!("p{}", index)
+}
+
+/// The routes of one method.
+///
+/// Paths are found by walking a tree of segments, a segment with a typed or regex parameter
+/// is matched on its own. Paths with patterns that can't be split in to segments, like one
+/// that spans a `/`, are matched all at once after that.
+#[derive(Default)]
+struct Routes {
+    /// Every route, in the order they were added.
+    all: Vec<Route>,
+    /// The routes that can be split in to segments, as indexes in to `all`.
+    tree: Node,
+    /// The routes with patterns across segments, as indexes in to `all`.
+    patterns: Vec<usize>,
+    /// Every pattern across segments in one set, built once all routes are added.
+    ///
+    /// Without it the patterns are tried one by one.
+    set: Option<RegexSet>,
+}
+
+impl Routes {
+    fn push(&mut self, route: Route) -> &mut Route {
+        let index = self.all.len();
+
+        match Segment::split(&route.path) {
+            Some(segments) => self.tree.insert(segments, index),
+            None => self.patterns.push(index),
+        }
+
+        self.all.push(route);
+        self.set = None;
+
+        &mut self.all[index]
+    }
+
+    /// Build the set of patterns across segments.
+    fn build(&mut self) {
+        let all = &self.all;
+
+        self.set = RegexSet::new(self.patterns.iter().map(|&index| all[index].pattern.as_str())).ok();
+    }
+
+    fn find(&self, path: &str) -> Option<(&Route, Capture)> {
+        if let Some(segments) = split(path) {
+            let mut values = Vec::new();
+
+            if let Some(index) = self.tree.find(&segments, &mut values) {
+                let route = &self.all[index];
+
+                return Some((route, route.capture(&values)));
+            }
+        }
+
+        match self.set {
+            // The set tells which patterns match in one pass, the first one added whose
+            // values fit their types wins
+            Some(ref set) => set
+                .matches(path)
+                .into_iter()
+                .map(|index| &self.all[self.patterns[index]])
+                .find_map(|route| Some((route, route.captures(path)?))),
+            None => self
+                .patterns
+                .iter()
+                .map(|&index| &self.all[index])
+                .find_map(|route| Some((route, route.captures(path)?))),
+        }
+    }
+}
+
+/// A segment of a route path.
+enum Segment {
+    /// Matches only this text.
+    Static(String),
+    /// An `<id>`, matches any non empty segment.
+    Param,
+    /// A segment with typed or regex parameters, matched by a regex of the segment alone.
+    Pattern(Matcher),
+}
+
+/// The regex of a segment and the types of its parameters, in order.
+struct Matcher {
+    regex: Regex,
+    kinds: Vec<Option<Kind>>,
+}
+
+impl Matcher {
+    /// Match a segment, pushing the value of each parameter or None if its group didn't match.
+    fn values<'p>(&self, segment: &'p str, values: &mut Vec<Option<&'p str>>) -> bool {
+        let caps = match self.regex.captures(segment) {
+            Some(caps) => caps,
+            None => return false,
+        };
+
+        for (index, kind) in self.kinds.iter().enumerate() {
+            let value = caps.name(&group(index)).map(|value| value.as_str());
+
+            // A value that doesn't fit its type leaves the segment to other routes
+            if let (Some(kind), Some(value)) = (kind, value) {
+                if kind.value(value).is_none() {
+                    return false;
+                }
+            }
+
+            values.push(value);
+        }
+
+        true
+    }
+}
+
+impl Segment {
+    /// Split a route path in to segments, or None if any part of it needs a regex across segments.
+    fn split(path: &str) -> Option<Vec<Segment>> {
+        if path.is_empty() {
+            return Some(Vec::new());
+        }
+
+        let path = path.strip_prefix('/')?;
+        let path = path.strip_suffix('/').unwrap_or(path);
+
+        if path.is_empty() {
+            return Some(Vec::new());
+        }
+
+        // Slashes inside a `<...>` belong to its regex
+        let mut segments = Vec::new();
+        let mut start = 0;
+        let mut inside = false;
+
+        for (i, c) in path.char_indices() {
+            match c {
+                '<' => inside = true,
+                '>' => inside = false,
+                '/' if !inside => {
+                    segments.push(&path[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+
+        segments.push(&path[start..]);
+
+        segments.into_iter().map(Segment::read).collect()
+    }
+
+    /// Read one segment of a route path.
+    fn read(segment: &str) -> Option<Segment> {
+        if is_static(segment) {
+            return Some(Segment::Static(segment.to_string()));
+        }
+
+        let (ids, pattern) = Router::parse(segment);
+
+        // Text around the parameters is matched as it is written
+        let mut text = String::new();
+        let mut regex = String::from("^");
+        let mut rest = pattern.as_str();
+
+        for (index, id) in ids.iter().enumerate() {
+            let group = format!("(?P<{}>{})", group(index), id.pattern);
+            let at = rest.find(&group)?;
+
+            text.push_str(&rest[..at]);
+            regex.push_str(&regex::escape(&rest[..at]));
+            regex.push_str(&group);
+            rest = &rest[at + group.len()..];
+        }
+
+        text.push_str(rest);
+        regex.push_str(&regex::escape(rest));
+        regex.push('$');
+
+        if ids.is_empty() || !(text.is_empty() || is_static(&text)) {
+            return None;
+        }
+
+        if let [id] = &ids[..] {
+            if id.kind.is_none() && id.pattern == "[^/]+" && text.is_empty() {
+                return Some(Segment::Param);
+            }
+        }
+
+        for id in &ids {
+            if !in_segment(&regex_syntax::parse(&id.pattern).ok()?) {
+                return None;
+            }
+        }
+
+        Some(Segment::Pattern(Matcher {
+            regex: Regex::new(&regex).ok()?,
+            kinds: ids.iter().map(|id| id.kind).collect(),
+        }))
+    }
+
+    /// Check if two segments match the same text the same way, so they can share a node.
+    fn same(&self, other: &Segment) -> bool {
+        match (self, other) {
+            (Segment::Static(a), Segment::Static(b)) => a == b,
+            (Segment::Param, Segment::Param) => true,
+            (Segment::Pattern(a), Segment::Pattern(b)) => {
+                a.regex.as_str() == b.regex.as_str() && a.kinds == b.kinds
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if this segment matches all the text the other one does.
+    fn covers(&self, other: &Segment) -> bool {
+        match (self, other) {
+            (Segment::Param, Segment::Static(_)) | (Segment::Param, Segment::Param) => true,
+            (Segment::Param, Segment::Pattern(matcher)) => !matcher.regex.is_match(""),
+            (Segment::Pattern(matcher), Segment::Static(text)) => matcher.values(text, &mut Vec::new()),
+            (Segment::Pattern(a), Segment::Pattern(b)) => {
+                a.regex.as_str() == b.regex.as_str()
+                    && a.kinds.iter().zip(&b.kinds).all(|(a, b)| a.is_none() || a == b)
+            }
+            (a, b) => a.same(b),
+        }
+    }
+
+    /// Match a segment of a url path, pushing the values of its parameters.
+    fn matches<'p>(&self, segment: &'p str, values: &mut Vec<Option<&'p str>>) -> bool {
+        match self {
+            Segment::Static(text) => text == segment,
+            Segment::Param if segment.is_empty() => false,
+            Segment::Param => {
+                values.push(Some(segment));
+                true
+            }
+            Segment::Pattern(matcher) => matcher.values(segment, values),
+        }
+    }
+}
+
+/// Check if text holds nothing but literal characters of a path segment.
+fn is_static(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-._~%@!,;='".contains(c))
+}
+
+/// Check if a regex only ever matches within one segment, never a `/` or a position in the path.
+fn in_segment(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty => true,
+        HirKind::Literal(literal) => !literal.0.contains(&b'/'),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .iter()
+            .all(|range| !(range.start()..=range.end()).contains(&'/')),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .iter()
+            .all(|range| !(range.start()..=range.end()).contains(&b'/')),
+        HirKind::Look(_) => false,
+        HirKind::Repetition(repetition) => in_segment(&repetition.sub),
+        HirKind::Capture(capture) => in_segment(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().all(in_segment),
+    }
+}
+
+/// Split a url path in to its segments, ignoring a trailing slash.
+fn split(path: &str) -> Option<Vec<&str>> {
+    let path = path.strip_prefix('/')?;
+
+    if path.is_empty() {
+        return Some(Vec::new());
+    }
+
+    Some(path.strip_suffix('/').unwrap_or(path).split('/').collect())
+}
+
+/// A node in the segment tree, one level per segment.
+#[derive(Default)]
+struct Node {
+    /// The route for the path ending at this node.
+    route: Option<usize>,
+    statics: HashMap<String, Node>,
+    /// Segments with parameters, `<id>` or patterns, in the order they were added.
+    dynamics: Vec<(Segment, Node)>,
+}
+
+impl Node {
+    fn insert(&mut self, mut segments: Vec<Segment>, index: usize) {
+        if segments.is_empty() {
+            // The first route added for a path is the one used
+            let _ = self.route.get_or_insert(index);
+            return;
+        }
+
+        let rest = segments.split_off(1);
+
+        match segments.pop() {
+            Some(Segment::Static(segment)) => {
+                self.statics.entry(segment).or_default().insert(rest, index)
+            }
+            Some(segment) => {
+                let found = self.dynamics.iter().position(|(other, _)| other.same(&segment));
+
+                let position = found.unwrap_or_else(|| {
+                    self.dynamics.push((segment, Node::default()));
+                    self.dynamics.len() - 1
+                });
+
+                self.dynamics[position].1.insert(rest, index)
+            }
+            None => {}
+        }
+    }
+
+    /// The node a segment of an added route leads to.
+    fn child(&self, segment: &Segment) -> Option<&Node> {
+        match segment {
+            Segment::Static(text) => self.statics.get(text),
+            _ => self
+                .dynamics
+                .iter()
+                .find(|(other, _)| other.same(segment))
+                .map(|(_, node)| node),
+        }
+    }
+
+    /// Check if a route added earlier is tried before another one and matches all of its paths,
+    /// Some(true) if their segments are the same.
+    fn shadows(&self, earlier: &[Segment], route: &[Segment]) -> Option<bool> {
+        if earlier.len() != route.len() {
+            return None;
+        }
+
+        let mut node = self;
+
+        for (at, (a, b)) in earlier.iter().zip(route).enumerate() {
+            if a.same(b) {
+                node = node.child(a)?;
+                continue;
+            }
+
+            // Where the routes part only parameters are tried in order, plain text always goes first
+            let position = |segment: &Segment| {
+                node.dynamics.iter().position(|(other, _)| other.same(segment))
+            };
+            let first = position(a)? < position(b)?;
+
+            return if first && earlier[at..].iter().zip(&route[at..]).all(|(a, b)| a.covers(b)) {
+                Some(false)
+            } else {
+                None
+            };
+        }
+
+        Some(true)
+    }
+
+    /// Find the route for the segments, static segments are tried before parameters
+    /// and parameters in the order they were added.
+    fn find<'p>(&self, segments: &[&'p str], values: &mut Vec<Option<&'p str>>) -> Option<usize> {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return self.route,
+        };
+
+        if let Some(index) = self.statics.get(*segment).and_then(|node| node.find(rest, values)) {
+            return Some(index);
         }
-    }
 
-    /// Get a value based on key.
-    ///
-    /// This is a wrapper around try_get.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # use direkuta::prelude::*;
-    /// let mut capture = Capture::new();
-    ///
-    /// capture.set("message", "Hello World!");
-    ///
-    /// println!("{}", capture.get("message"));
-    /// ```
-    ///
-    /// # Panics
-    ///
-    /// If the key does not exist the function will panic.
-    ///
-    /// If you do not know if the key exists use `try_get`.
-    pub fn get(&self, key: impl Into<String>) -> &str {
-        let key = key.into();
-        self.try_get(key.as_str())
-            .unwrap_or_else(|| panic!("Key not found in captures: {}", key))
+        let len = values.len();
+
+        for (dynamic, node) in &self.dynamics {
+            if dynamic.matches(segment, values) {
+                if let Some(index) = node.find(rest, values) {
+                    return Some(index);
+                }
+            }
+
+            values.truncate(len);
+        }
+
+        None
     }
+}
 
-    /// Attempt to get a value based on key as type.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # use direkuta::prelude::*;
-    /// let mut capture = Capture::new();
-    ///
-    /// capture.set("message", "0");
-    ///
-    /// println!("{:?}", capture.try_get_parse::<u8>("message"));
-    /// ```
-    ///
-    /// # Panics
-    ///
-    /// If the key does not exist the function will panic.
-    pub fn try_get_parse<T: ::std::str::FromStr>(&self, key: &str) -> Option<T>
-    where
-        T::Err: ::std::fmt::Debug
-    {
-        match self.try_get(key) {
-            Some(s) => {
-                let c = s.parse::<T>()
-                    .unwrap_or_else(|_| panic!("Error parsing key to type: {}", key));
-                Some(c)
-            },
-            None => None,
+/// Sets options for a route after it has been added.
+///
+/// # Examples
+///
+/// ```rust
+/// # use direkuta::prelude::*;
+/// use std::time::Duration;
+///
+/// Direkuta::new()
+///     .route(|r| {
+///         r.get("/report", |_, _, _| {
+///             Response::new().with_body("Report").build()
+///         }).timeout(Duration::from_secs(60));
+///     });
+/// ```
+pub struct RouteBuilder<'a> {
+    /// Missing when the route's pattern is invalid.
+    route: Option<&'a mut Route>,
+}
+
+impl RouteBuilder<'_> {
+    /// Set how long the handler may run, overriding `Config::request_timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        if let Some(ref mut route) = self.route {
+            route.timeout = Some(timeout);
         }
+
+        self
     }
 
-    /// Get a value based on key as type.
-    ///
-    /// This is a wrapper around try_get_parse.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// # use direkuta::prelude::*;
-    /// let mut capture = Capture::new();
-    ///
-    /// capture.set("message", "0");
-    ///
-    /// println!("{}", capture.get_parse::<u8>("message"));
-    /// ```
-    ///
-    /// # Panics
-    ///
-    /// If the key does not exist the function will panic.
-    ///
-    /// If you do not know if the key exists use `try_get_parse`.
-    pub fn get_parse<T: ::std::str::FromStr>(&self, key: &str) -> T
-    where
-        T::Err: ::std::fmt::Debug
-    {
-        self.try_get_parse(key)
-            .unwrap_or_else(|| panic!("Key not found in captures: {}", key))
+    /// Set the largest request body in bytes, overriding `Config::max_body_size`.
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        if let Some(ref mut route) = self.route {
+            route.max_body_size = Some(size);
+        }
+
+        self
     }
-}
 
-impl Default for Capture {
-    fn default() -> Capture {
-        Capture {
-            inner: IndexMap::new(),
+    /// Set how many requests this route may handle at once, on top of `Config::max_concurrency`.
+    pub fn max_concurrency(mut self, limit: usize) -> Self {
+        if let Some(ref mut route) = self.route {
+            route.max_concurrency = Some(limit);
         }
+
+        self
     }
-}
 
-type Handler =
-    Fn(Request, Arc<State>, Capture)
-            -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static>
-        + Send
-        + Sync
-        + 'static;
+    /// Name the route so its URL can be built with `Urls::url_for`.
+    ///
+    /// The name is kept when the route is moved under `Router::path`.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        if let Some(ref mut route) = self.route {
+            route.name = Some(name.into());
+        }
 
-/// Internal route, stores the handler and path details.
-///
-/// This is not to be used directly, it is only used for Direkuta.route.
-struct Route {
-    handler: Box<Handler>,
-    ids: Vec<String>,
-    path: String,
-    pattern: Regex,
+        self
+    }
 }
 
 /// Router.
@@ -833,7 +3851,13 @@ struct Route {
 /// }
 /// ```
 pub struct Router {
-    inner: IndexMap<Method, Vec<Route>>,
+    inner: IndexMap<Method, Routes>,
+    /// Handlers for requests no route matches, each covering the path it was added under.
+    fallbacks: Vec<Route>,
+    /// Pattern errors, reported when the server is built.
+    errors: Vec<DireError>,
+    /// The URLs of the named routes, filled in by `build`.
+    urls: Urls,
 }
 
 impl Router {
@@ -866,8 +3890,35 @@ impl Router {
     /// }
     /// ```
     ///
+    /// ## Parameter
+    ///
+    /// A plain `<id>` matches one segment of the path.
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// # use direkuta::prelude::hyper::*;
+    /// Direkuta::new()
+    ///     .route(|r| {
+    ///         r.route(Method::GET, "/users/<id>", |_, _, c| {
+    ///             Response::new().with_body(c.get("id")).build()
+    ///         });
+    ///     });
+    /// ```
+    ///
+    /// ```rust,ignore
+    /// "/users/5" : { "id" => "5" } {
+    ///     GET => "5"
+    /// }
+    /// ```
+    ///
     /// ## Regex
     ///
+    /// A segment with a regex is tried after plain text, along with `<id>` parameters in the same
+    /// place in the order they were added.
+    /// Regexes that can match a `/` are only tried when no route in the tree matches,
+    /// if several match the first one added is used.
+    /// The id captures all its regex matches, groups inside the regex are ignored.
+    ///
     /// ```rust
     /// # use direkuta::prelude::*;
     /// # use direkuta::prelude::hyper::*;
@@ -893,18 +3944,32 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
+    ) -> RouteBuilder<'_> {
         let path = path.into();
 
         // Transform the path in to ids and regex
-        let reader = self.read(&path);
+        match self.read(&path) {
+            Ok((ids, pattern)) => {
+                let route = self.inner.entry(method).or_default().push(Route {
+                    handler: Box::new(handler),
+                    ids,
+                    name: None,
+                    path,
+                    pattern,
+                    timeout: None,
+                    max_body_size: None,
+                    max_concurrency: None,
+                    in_flight: Arc::new(AtomicUsize::new(0)),
+                });
+
+                RouteBuilder { route: Some(route) }
+            }
+            Err(e) => {
+                self.errors.push(e);
 
-        self.inner.entry(method).or_insert(Vec::new()).push(Route {
-            handler: Box::new(handler),
-            ids: reader.0,
-            path,
-            pattern: reader.1,
-        });
+                RouteBuilder { route: None }
+            }
+        }
     }
 
     /// Adds a GET request handler.
@@ -955,8 +4020,8 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::GET, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::GET, path, handler)
     }
 
     /// Adds a POST request handler.
@@ -988,8 +4053,8 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::POST, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::POST, path, handler)
     }
 
     /// Adds a PUT request handler.
@@ -1021,8 +4086,8 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::PUT, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::PUT, path, handler)
     }
 
     /// Adds a DELETE request handler.
@@ -1054,12 +4119,15 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::DELETE, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::DELETE, path, handler)
     }
 
     /// Adds a HEAD request handler.
     ///
+    /// GET handlers already answer HEAD requests without their body,
+    /// this is only needed to answer them differently.
+    ///
     /// # Examples
     ///
     /// ## Simple
@@ -1087,12 +4155,14 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::HEAD, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::HEAD, path, handler)
     }
 
     /// Adds a OPTIONS request handler.
     ///
+    /// Without one, OPTIONS requests are answered with the allowed methods in the `Allow` header.
+    ///
     /// # Examples
     ///
     /// ## Simple
@@ -1120,8 +4190,74 @@ impl Router {
             + Send
             + Sync
             + 'static,
-    ) {
-        self.route(Method::OPTIONS, path, handler);
+    ) -> RouteBuilder<'_> {
+        self.route(Method::OPTIONS, path, handler)
+    }
+
+    /// Adds a handler for requests that no route matches, instead of an empty 404.
+    ///
+    /// Inside a path the fallback only covers urls under that path,
+    /// the fallback of the innermost matching path is used.
+    ///
+    /// Urls that have routes for other methods get `405 Method Not Allowed` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// Direkuta::new()
+    ///     .route(|r| {
+    ///         r.fallback(|_, _| {
+    ///             Response::new().with_status(404).with_body("Page not found").build()
+    ///         });
+    ///         r.path("/api", |r| {
+    ///             r.fallback(|_, _| {
+    ///                 Response::new().with_status(404).with_body("Unknown endpoint").build()
+    ///             });
+    ///         });
+    ///     });
+    /// ```
+    ///
+    /// ```rust,ignore
+    /// "/missing" : {  } {
+    ///     GET => "Page not found"
+    /// }
+    /// "/api/missing" : {  } {
+    ///     GET => "Unknown endpoint"
+    /// }
+    /// ```
+    pub fn fallback(
+        &mut self,
+        handler: impl Fn(Request, Arc<State>)
+                -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static>
+            + Send
+            + Sync
+            + 'static,
+    ) -> RouteBuilder<'_> {
+        match self.read_scope("") {
+            Ok((ids, pattern)) => {
+                self.fallbacks.push(Route {
+                    handler: Box::new(move |req, state, _| handler(req, state)),
+                    ids,
+                    name: None,
+                    path: String::new(),
+                    pattern,
+                    timeout: None,
+                    max_body_size: None,
+                    max_concurrency: None,
+                    in_flight: Arc::new(AtomicUsize::new(0)),
+                });
+
+                RouteBuilder {
+                    route: self.fallbacks.last_mut(),
+                }
+            }
+            Err(e) => {
+                self.errors.push(e);
+
+                RouteBuilder { route: None }
+            }
+        }
     }
 
     /// Create a path for multiple request types.
@@ -1158,113 +4294,312 @@ impl Router {
 
         let path = path.into();
 
+        self.errors.extend(builder.errors);
+
         // Loop through new methods
         for (method, routes) in builder.inner {
             // Loop through new routes
-            for route in routes {
+            for route in routes.all {
                 // Concatenate paths
                 let n_path = format!("{}{}", path, route.path);
 
                 // Transform the path in to ids and regex
-                let reader = self.read(&n_path);
-
-                self.inner
-                    .entry(method.clone())
-                    .or_insert(Vec::new())
-                    .push(Route {
-                        handler: route.handler,
-                        ids: reader.0,
-                        path: n_path,
-                        pattern: reader.1,
-                    });
+                match self.read(&n_path) {
+                    Ok((ids, pattern)) => {
+                        let _ = self.inner.entry(method.clone()).or_default().push(Route {
+                            ids,
+                            path: n_path,
+                            pattern,
+                            ..route
+                        });
+                    }
+                    Err(e) => self.errors.push(e),
+                }
+            }
+        }
+
+        for route in builder.fallbacks {
+            let n_path = format!("{}{}", path, route.path);
+
+            match self.read_scope(&n_path) {
+                Ok((ids, pattern)) => self.fallbacks.push(Route {
+                    ids,
+                    path: n_path,
+                    pattern,
+                    ..route
+                }),
+                Err(e) => self.errors.push(e),
             }
         }
     }
 
-    /// When a request is received this is called to find a handler.
-    #[inline]
-    fn recognize(&self, method: &Method, path: &str) -> Result<(&Handler, Capture), StatusCode> {
-        // Get method
-        let routes = self.inner.get(method).ok_or(StatusCode::NOT_FOUND)?;
-
-        // Loop through all routes of method
-        for route in routes.iter() {
-            // Make sure the route matches
-            if route.pattern.is_match(path) {
-                // Get the capture map
-                if let Some(map) = self.captures(&route, &route.pattern, path) {
-                    return Ok((&*route.handler, map));
+    /// Prepare the routes for matching, once they have all been added.
+    fn build(&mut self) {
+        for routes in self.inner.values_mut() {
+            routes.build();
+        }
+
+        self.validate();
+
+        let mut urls = IndexMap::new();
+
+        let named = self
+            .inner
+            .values()
+            .flat_map(|routes| routes.all.iter())
+            .chain(self.fallbacks.iter())
+            .filter_map(|route| Some((route.name.as_ref()?, route)));
+
+        for (name, route) in named {
+            if urls.contains_key(name) {
+                self.errors
+                    .push(DireError::Url(format!("The route name `{}` is used twice", name)));
+                continue;
+            }
+
+            match Urls::parts(route) {
+                Ok(parts) => {
+                    let _ = urls.insert(name.clone(), parts);
                 }
+                Err(e) => self.errors.push(e),
             }
         }
 
-        Err(StatusCode::NOT_FOUND)
+        self.urls = Urls {
+            inner: Arc::new(urls),
+        };
     }
 
-    /// Takes each capture and transforms it into a map of ids and captures.
-    #[inline]
-    fn captures(&self, route: &Route, re: &Regex, path: &str) -> Option<Capture> {
-        // Get captures.
-        re.captures(path).map(|caps| {
-            let mut captures = Capture::new();
-
-            // Loop through each capture
-            for (i, _) in caps.iter().enumerate() {
-                // We dont want the first whole capture.
-                if i != 0 {
-                    // Insert the capture to its id.
-                    captures.set(
-                        // An id exists so the unwrap is safe.
-                        route.ids[i - 1].as_str(),
-                        // The capture exists so the unwrap is safe.
-                        caps.get(i).unwrap().as_str(),
-                    );
+    /// Check every route, reporting routes that can never be matched.
+    ///
+    /// A route in the segment tree is unreachable when an earlier route is tried first at the
+    /// segment where they part and matches every path it does, like `/<name>` before `/<id:int>`.
+    /// Routes with patterns across segments and fallbacks are unreachable when an earlier one has
+    /// the same pattern and types, or the same pattern without a type where it has one.
+    fn validate(&mut self) {
+        fn check<'a>(
+            routes: impl Iterator<Item = &'a Route>,
+            kind: &str,
+            errors: &mut Vec<DireError>,
+        ) {
+            let mut seen: Vec<(String, &'a Route)> = Vec::new();
+
+            for route in routes {
+                let shape = route.shape();
+
+                let found = seen
+                    .iter()
+                    .find(|(other, earlier)| *other == shape && earlier.covers(route));
+
+                match found {
+                    Some((_, other)) => errors.push(DireError::Route(
+                        route.path.clone(),
+                        format!(
+                            "is unreachable, the {} `{}` matches the same paths",
+                            kind, other.path
+                        ),
+                    )),
+                    None => seen.push((shape, route)),
+                }
+            }
+        }
+
+        for (method, routes) in &self.inner {
+            let segments: Vec<_> =
+                routes.all.iter().map(|route| Segment::split(&route.path)).collect();
+            let mut unreachable = vec![false; routes.all.len()];
+
+            for (index, route) in routes.all.iter().enumerate() {
+                let tail = match segments[index] {
+                    Some(ref tail) => tail,
+                    None => continue,
+                };
+
+                // A route that is never matched can't shadow others, the one before it is reported
+                let found = (0..index).filter(|&earlier| !unreachable[earlier]).find_map(|earlier| {
+                    let same = routes.tree.shadows(segments[earlier].as_ref()?, tail)?;
+
+                    Some((&routes.all[earlier], same))
+                });
+
+                if let Some((other, same)) = found {
+                    let reason = if same {
+                        "matches the same paths"
+                    } else {
+                        "is tried first and matches all of its paths"
+                    };
+
+                    unreachable[index] = true;
+                    self.errors.push(DireError::Route(
+                        route.path.clone(),
+                        format!("is unreachable, the {} route `{}` {}", method, other.path, reason),
+                    ));
                 }
             }
 
-            if cfg!(debug_assertions) {
-                captures.set("debug_pattern", re.as_str());
+            let patterns = routes.patterns.iter().map(|&index| &routes.all[index]);
+
+            check(patterns, &format!("{} route", method), &mut self.errors);
+        }
+
+        check(self.fallbacks.iter(), "fallback", &mut self.errors);
+    }
+
+    /// When a request is received this is called to find a handler.
+    #[inline]
+    fn recognize(&self, method: &Method, path: &str) -> Result<(&Route, Capture), StatusCode> {
+        if let Some(found) = self.find(method, path) {
+            return Ok(found);
+        }
+
+        // HEAD is answered by the GET handler, the body is dropped later
+        if method == Method::HEAD {
+            if let Some(found) = self.find(&Method::GET, path) {
+                return Ok(found);
+            }
+        }
+
+        // The path exists, just not for this method
+        if !self.allowed(path).is_empty() {
+            return Err(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        // The longest path is the innermost scope
+        self.fallbacks
+            .iter()
+            .filter(|route| route.pattern.is_match(path))
+            .max_by_key(|route| route.path.len())
+            .and_then(|route| Some((route, route.captures(path)?)))
+            .ok_or(StatusCode::NOT_FOUND)
+    }
+
+    /// Finds the route of the method that matches the path.
+    fn find(&self, method: &Method, path: &str) -> Option<(&Route, Capture)> {
+        self.inner.get(method)?.find(path)
+    }
+
+    /// Returns the methods that have a route for the path.
+    ///
+    /// HEAD and OPTIONS are included when they are answered automatically.
+    fn allowed(&self, path: &str) -> Vec<Method> {
+        let mut allowed = self
+            .inner
+            .iter()
+            .filter(|(_, routes)| routes.find(path).is_some())
+            .map(|(method, _)| method.clone())
+            .collect::<Vec<_>>();
+
+        if allowed.is_empty() {
+            return allowed;
+        }
+
+        if allowed.contains(&Method::GET) && !allowed.contains(&Method::HEAD) {
+            allowed.push(Method::HEAD);
+        }
+
+        if !allowed.contains(&Method::OPTIONS) {
+            allowed.push(Method::OPTIONS);
+        }
+
+        allowed
+    }
+
+    /// Parse each path into a vector of ids and a regex pattern
+    #[inline]
+    fn read(&self, path: &str) -> Result<(Vec<Id>, Regex), DireError> {
+        let (ids, pattern) = Router::parse(path);
+
+        self.check_ids(path, &ids)?;
+
+        match Regex::new(&self.normalize(&pattern)) {
+            Ok(r) => Ok((ids, r)),
+            Err(e) => Err(DireError::Pattern(path.to_string(), e)),
+        }
+    }
+
+    /// Transforms a path in to ids and a regex that matches it and anything under it.
+    fn read_scope(&self, path: &str) -> Result<(Vec<Id>, Regex), DireError> {
+        let (ids, pattern) = Router::parse(path);
+
+        self.check_ids(path, &ids)?;
+
+        let pattern = pattern
+            .trim()
+            .trim_start_matches('^')
+            .trim_end_matches('$')
+            .trim_end_matches('/');
+
+        match Regex::new(&format!("^{}(?:/.*)?$", pattern)) {
+            Ok(r) => Ok((ids, r)),
+            Err(e) => Err(DireError::Pattern(path.to_string(), e)),
+        }
+    }
+
+    /// Make sure no id is used twice, as each is set from its own group in the route's regex.
+    fn check_ids(&self, path: &str, ids: &[Id]) -> Result<(), DireError> {
+        for (i, id) in ids.iter().enumerate() {
+            if ids[..i].iter().any(|other| other.name == id.name) {
+                return Err(DireError::Route(
+                    path.to_string(),
+                    format!("uses the id `{}` twice", id.name),
+                ));
             }
+        }
 
-            captures
-        })
+        Ok(())
     }
 
-    /// Parse each path into a vector of ids and a regex pattern
-    #[inline]
-    fn read(&self, path: &str) -> (Vec<String>, Regex) {
-        let mut ids: Vec<String> = Vec::new();
+    /// Splits a path in to its ids and a regex pattern.
+    fn parse(path: &str) -> (Vec<Id>, String) {
+        let mut ids: Vec<Id> = Vec::new();
         let mut pattern = String::new();
 
         let mut mode = Mode::Look;
         let mut id = String::new();
+        let mut regex = String::new();
 
         for c in path.chars() {
-            match c {
-                '<' => mode = Mode::Id,
-                ':' => {
-                    mode = Mode::Regex;
-                    ids.push(id.clone());
-                    id.clear();
+            match (c, &mode) {
+                ('<', Mode::Look) => mode = Mode::Id,
+                (':', Mode::Id) => mode = Mode::Regex,
+                ('>', Mode::Id) => {
+                    // A plain `<id>` matches one segment
+                    pattern.push_str(&format!("(?P<{}>[^/]+)", group(ids.len())));
+
+                    ids.push(Id {
+                        name: mem::take(&mut id),
+                        kind: None,
+                        pattern: "[^/]+".to_string(),
+                    });
+
+                    mode = Mode::Look;
                 }
-                '>' => mode = Mode::Look,
-                _ => match mode {
-                    Mode::Id => id.push(c),
-                    Mode::Regex | Mode::Look => pattern.push(c),
-                },
+                ('>', Mode::Regex) => {
+                    // Built in types stand in for their regex
+                    let kind = Kind::from_name(&regex);
+
+                    if let Some(kind) = kind {
+                        regex = kind.pattern().to_string();
+                    }
+
+                    // The id has a group of its own, so groups inside the regex don't shift it
+                    pattern.push_str(&format!("(?P<{}>{})", group(ids.len()), regex));
+
+                    ids.push(Id {
+                        name: mem::take(&mut id),
+                        kind,
+                        pattern: mem::take(&mut regex),
+                    });
+
+                    mode = Mode::Look;
+                }
+                (_, Mode::Id) => id.push(c),
+                (_, Mode::Regex) => regex.push(c),
+                (_, Mode::Look) => pattern.push(c),
             }
         }
 
-        (
-            ids,
-            match Regex::new(&self.normalize(&pattern)) {
-                Ok(r) => r,
-                Err(e) => {
-                    eprintln!("Regex pattern error: {}", e);
-                    ::std::process::exit(1);
-                }
-            },
-        )
+        (ids, pattern)
     }
 
     /// Normalizes the regex paths.
@@ -1272,12 +4607,12 @@ impl Router {
     /// Removes the beginning `^` and ending `$` and `/`, if the exist.
     /// Then adds them even if they weren't there.
     #[inline]
-    fn normalize(&self, pattern: &str) -> Cow<str> {
+    fn normalize(&self, pattern: &str) -> Cow<'_, str> {
         let pattern = pattern
             .trim()
-            .trim_left_matches('^')
-            .trim_right_matches('$')
-            .trim_right_matches('/');
+            .trim_start_matches('^')
+            .trim_end_matches('$')
+            .trim_end_matches('/');
         match pattern {
             "" => "^/$".into(),
             s => format!("^{}/?$", s).into(),
@@ -1285,10 +4620,222 @@ impl Router {
     }
 }
 
+/// The URLs of the named routes, so links don't have to be written out by hand.
+///
+/// Once the server is built it can be taken from State,
+/// templates can use it through the `url_for` function.
+///
+/// # Examples
+///
+/// ```rust
+/// # use direkuta::prelude::*;
+/// # use direkuta::prelude::test::*;
+/// let mut client = TestClient::new(Direkuta::new().route(|r| {
+///     r.get("/users/<id:int>", |_, _, _| {
+///         Response::new().build()
+///     }).name("user");
+///     r.get("/", |_, s, _| {
+///         let url = s.get::<Urls>().url_for("user", &[("id", "5")]).unwrap();
+///
+///         Response::new().with_body(url).build()
+///     });
+/// }));
+///
+/// assert_eq!(client.get("/").send().unwrap().text(), "/users/5");
+/// ```
+#[derive(Clone, Default)]
+pub struct Urls {
+    inner: Arc<IndexMap<String, Vec<Part>>>,
+}
+
+/// A piece of a named route's path.
+enum Part {
+    Text(String),
+    /// A parameter, the regex its values have to match and if its values can hold a `/`.
+    Param(String, Regex, bool),
+}
+
+impl Urls {
+    /// Build the URL of a named route, filling in its parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DireError::Url` if there is no route with the name,
+    /// if a parameter is missing or unknown or if a value does not match its parameter.
+    ///
+    /// Values are percent encoded, so they can't add a query or change the path,
+    /// a `/` is only kept for parameters that match across segments like `<rest:path>`.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, DireError> {
+        let parts = self
+            .inner
+            .get(name)
+            .ok_or_else(|| DireError::Url(format!("No route is named `{}`", name)))?;
+
+        if let Some((key, _)) = params.iter().find(|(key, _)| {
+            !parts
+                .iter()
+                .any(|part| matches!(part, Part::Param(id, _, _) if id == key))
+        }) {
+            return Err(DireError::Url(format!(
+                "The route `{}` has no parameter `{}`",
+                name, key
+            )));
+        }
+
+        let mut url = String::new();
+
+        for part in parts.iter() {
+            match part {
+                Part::Text(text) => url.push_str(text),
+                Part::Param(id, pattern, slashes) => {
+                    let value = params
+                        .iter()
+                        .find(|(key, _)| key == id)
+                        .map(|(_, value)| *value)
+                        .ok_or_else(|| {
+                            DireError::Url(format!(
+                                "The route `{}` needs the parameter `{}`",
+                                name, id
+                            ))
+                        })?;
+
+                    // The encoded value is what the router will see
+                    let encoded = encode(value, *slashes);
+
+                    if !pattern.is_match(&encoded) {
+                        return Err(DireError::Url(format!(
+                            "`{}` does not match the parameter `{}` of the route `{}`",
+                            value, id, name
+                        )));
+                    }
+
+                    url.push_str(&encoded);
+                }
+            }
+        }
+
+        if url.is_empty() {
+            url.push('/');
+        }
+
+        Ok(url)
+    }
+
+    /// Split the route's path in to text and parameters.
+    ///
+    /// The text between parameters has to be plain, escaped characters are unescaped
+    /// and an optional trailing slash is left off.
+    fn parts(route: &Route) -> Result<Vec<Part>, DireError> {
+        let mut parts = Vec::new();
+        let rest = route.path.trim().trim_start_matches('^').trim_end_matches('$');
+        let mut rest = rest.strip_suffix("/?").unwrap_or(rest);
+
+        let text = |text: &str| {
+            literal(text).map(Part::Text).ok_or_else(|| {
+                DireError::Url(format!(
+                    "The route `{}` has a regex outside its parameters, its URL can't be built",
+                    route.path
+                ))
+            })
+        };
+
+        for id in &route.ids {
+            // Every id was read from a `<...>` so both ends are there
+            let start = rest.find('<').unwrap_or(rest.len());
+            let end = rest[start..].find('>').map_or(rest.len(), |end| start + end + 1);
+
+            parts.push(text(&rest[..start])?);
+
+            let slashes = regex_syntax::parse(&id.pattern).is_ok_and(|hir| !in_segment(&hir));
+
+            match Regex::new(&format!("^(?:{})$", id.pattern)) {
+                Ok(pattern) => parts.push(Part::Param(id.name.clone(), pattern, slashes)),
+                Err(e) => return Err(DireError::Pattern(route.path.clone(), e)),
+            }
+
+            rest = &rest[end..];
+        }
+
+        parts.push(text(rest)?);
+
+        Ok(parts)
+    }
+
+    /// The `url_for` template function, taking the route's name and its parameters.
+    #[cfg(feature = "html")]
+    fn tera_function(&self) -> tera::GlobalFn {
+        let urls = self.clone();
+
+        Box::new(move |mut args| {
+            let name = match args.remove("name") {
+                Some(tera::Value::String(name)) => name,
+                _ => return Err("url_for needs the route's `name`".into()),
+            };
+
+            let values = args
+                .iter()
+                .map(|(key, value)| match value {
+                    tera::Value::String(value) => (key.as_str(), value.clone()),
+                    value => (key.as_str(), value.to_string()),
+                })
+                .collect::<Vec<_>>();
+            let params = values
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect::<Vec<_>>();
+
+            urls.url_for(&name, &params)
+                .map(tera::Value::String)
+                .map_err(|e| e.to_string().into())
+        })
+    }
+}
+
+/// Turn the text of a route's path in to the text of its URL, or None if it holds a regex.
+fn literal(text: &str) -> Option<String> {
+    let mut literal = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // Escapes like `\d` are classes, not characters
+            '\\' => match chars.next() {
+                Some(c) if !c.is_alphanumeric() => literal.push(c),
+                _ => return None,
+            },
+            // A `.` matches itself in plain segments, so it is kept as it is written
+            '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '^' | '$' => return None,
+            c => literal.push(c),
+        }
+    }
+
+    Some(literal)
+}
+
+/// Percent encode a value for a URL path, keeping only unreserved characters and optionally `/`.
+fn encode(value: &str, slashes: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if slashes => encoded.push('/'),
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
 impl Default for Router {
     fn default() -> Router {
         Router {
             inner: IndexMap::new(),
+            fallbacks: Vec::new(),
+            errors: Vec::new(),
+            urls: Urls::default(),
         }
     }
 }
@@ -1593,7 +5140,7 @@ impl Response {
     /// Wrapper around 'into_hyper' to change it into a future response.
     pub fn build(
         self,
-    ) -> Box<Future<Item = response::Response<Body>, Error = DireError> + Send + 'static> {
+    ) -> Box<dyn Future<Item = response::Response<Body>, Error = DireError> + Send + 'static> {
         Box::new(future::ok(self.into_hyper()))
     }
 }
@@ -1606,6 +5153,7 @@ impl Default for Response {
 }
 
 /// A builder function for CSS Responses.
+#[derive(Default)]
 pub struct CssBuilder {
     inner: String,
 }
@@ -1628,15 +5176,8 @@ impl CssBuilder {
     }
 }
 
-impl Default for CssBuilder {
-    fn default() -> CssBuilder {
-        CssBuilder {
-            inner: String::new(),
-        }
-    }
-}
-
 /// A builder function for JS Responses.
+#[derive(Default)]
 pub struct JsBuilder {
     inner: String,
 }
@@ -1659,14 +5200,6 @@ impl JsBuilder {
     }
 }
 
-impl Default for JsBuilder {
-    fn default() -> JsBuilder {
-        JsBuilder {
-            inner: String::new(),
-        }
-    }
-}
-
 /// A builder for JSON responses.
 #[cfg(feature = "json")]
 pub struct JsonBuilder<T: Serialize + Send + Sync> {
@@ -1822,10 +5355,6 @@ impl Request {
         &self.parts.headers
     }
 
-    fn headers_mut(&mut self) -> &mut HeaderMap<HeaderValue> {
-        &mut self.parts.headers
-    }
-
     /// Returns Request parts
     pub fn parts(&self) -> &request::Parts {
         &self.parts
@@ -1855,6 +5384,346 @@ impl Request {
     pub fn into_body(self) -> Body {
         self.body
     }
+
+    /// Limit the body to the given size, returning a flag that is set once it is read past it.
+    ///
+    /// Returns None right away if the Content-Length is already too large.
+    fn limit_body(&mut self, limit: u64) -> Option<Arc<AtomicBool>> {
+        let length = self
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse::<u64>().ok());
+
+        let exceeded = Arc::new(AtomicBool::new(false));
+
+        match length {
+            Some(length) if length > limit => return None,
+            // Hyper makes sure the body is no longer than its Content-Length.
+            Some(_) => {}
+            None => {
+                let body = mem::replace(&mut self.body, Body::empty());
+
+                self.body = Body::wrap_stream(LimitedBody {
+                    body,
+                    limit,
+                    remaining: limit,
+                    exceeded: exceeded.clone(),
+                });
+            }
+        }
+
+        Some(exceeded)
+    }
+
+    /// Return the verified client certificate, if the client presented one.
+    ///
+    /// This is only set when `Config::tls_client_ca` is used.
+    #[cfg(feature = "tls")]
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.parts
+            .extensions
+            .get::<Arc<PeerCertificate>>()
+            .map(|cert| &**cert)
+    }
+}
+
+/// A request body that fails once it is longer than its limit.
+struct LimitedBody {
+    body: Body,
+    limit: u64,
+    remaining: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl Stream for LimitedBody {
+    type Item = Chunk;
+    type Error = DireError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let chunk = try_ready!(self.body.poll());
+
+        if let Some(ref chunk) = chunk {
+            let len = chunk.len() as u64;
+
+            if len > self.remaining {
+                self.exceeded.store(true, Ordering::SeqCst);
+                return Err(DireError::BodyTooLarge(self.limit));
+            }
+
+            self.remaining -= len;
+        }
+
+        Ok(Async::Ready(chunk))
+    }
+}
+
+/// A client certificate verified during the TLS handshake.
+#[cfg(feature = "tls")]
+#[derive(Clone, Debug)]
+pub struct PeerCertificate {
+    der: Vec<u8>,
+    subject: String,
+    alt_names: Vec<String>,
+    fingerprint: String,
+}
+
+#[cfg(feature = "tls")]
+impl PeerCertificate {
+    /// Parse a DER encoded certificate.
+    ///
+    /// This does not verify the certificate, it is meant for building test requests.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> Result<Self, DireError> {
+        let der = der.into();
+
+        let (subject, alt_names) = {
+            let (_, cert) = x509_parser::parse_x509_certificate(&der)
+                .map_err(|e| DireError::Tls(format!("Unable to parse certificate: {}", e)))?;
+
+            let alt_names = match cert.subject_alternative_name() {
+                Ok(Some(ext)) => ext.value.general_names.iter().filter_map(alt_name).collect(),
+                _ => Vec::new(),
+            };
+
+            (cert.subject().to_string(), alt_names)
+        };
+
+        let fingerprint = digest::digest(&digest::SHA256, &der)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        Ok(Self {
+            der,
+            subject,
+            alt_names,
+            fingerprint,
+        })
+    }
+
+    /// Return the certificate subject, e.g. `CN=client, O=Direkuta`.
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Return the DNS, email, URI, and IP subject alternative names.
+    pub fn alt_names(&self) -> &[String] {
+        &self.alt_names
+    }
+
+    /// Return the SHA-256 fingerprint of the certificate as lowercase hex.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// Return the DER encoded certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+}
+
+/// Format a subject alternative name, skipping kinds that have no plain text form.
+#[cfg(feature = "tls")]
+fn alt_name(name: &x509_parser::extensions::GeneralName<'_>) -> Option<String> {
+    use x509_parser::extensions::GeneralName;
+
+    match *name {
+        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => {
+            Some(name.to_string())
+        }
+        GeneralName::IPAddress(ip) if ip.len() == 4 => {
+            Some(IpAddr::from([ip[0], ip[1], ip[2], ip[3]]).to_string())
+        }
+        GeneralName::IPAddress(ip) if ip.len() == 16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(ip);
+            Some(IpAddr::from(octets).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Dispatches requests to a Direkuta without opening a socket.
+///
+/// Requests go through the same middleware and router as they would on a running server.
+///
+/// # Examples
+///
+/// ```rust
+/// # use direkuta::prelude::*;
+/// # use direkuta::prelude::test::*;
+/// let mut client = TestClient::new(Direkuta::new()
+///     .route(|r| {
+///         r.get("/", |_, _, _| {
+///             Response::new().with_body("Hello World!").build()
+///         });
+///     }));
+///
+/// let res = client.get("/").send().unwrap();
+///
+/// assert_eq!(res.status().as_u16(), 200);
+/// assert_eq!(res.text(), "Hello World!");
+/// ```
+pub struct TestClient {
+    app: Direkuta,
+    runtime: CurrentRuntime,
+}
+
+impl TestClient {
+    /// Constructs a new TestClient around a Direkuta.
+    ///
+    /// # Panics
+    ///
+    /// If the Direkuta cannot be built, see `Direkuta::build`.
+    pub fn new(app: Direkuta) -> Self {
+        Self {
+            app: app.build().unwrap_or_else(|e| panic!("Unable to build Direkuta: {}", e)),
+            runtime: CurrentRuntime::new().expect("Unable to create runtime"),
+        }
+    }
+
+    /// Start a request with the given method and path.
+    pub fn request(&mut self, method: Method, path: impl Into<String>) -> TestRequest<'_> {
+        let mut builder = request::Builder::new();
+        let _ = builder.method(method).uri(path.into().as_str());
+
+        TestRequest {
+            client: self,
+            builder,
+            body: Body::empty(),
+        }
+    }
+
+    /// Start a GET request.
+    pub fn get(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::GET, path)
+    }
+
+    /// Start a POST request.
+    pub fn post(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::POST, path)
+    }
+
+    /// Start a PUT request.
+    pub fn put(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::PUT, path)
+    }
+
+    /// Start a DELETE request.
+    pub fn delete(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::DELETE, path)
+    }
+
+    /// Start a HEAD request.
+    pub fn head(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::HEAD, path)
+    }
+
+    /// Start a OPTIONS request.
+    pub fn options(&mut self, path: impl Into<String>) -> TestRequest<'_> {
+        self.request(Method::OPTIONS, path)
+    }
+}
+
+/// A request builder for TestClient.
+pub struct TestRequest<'a> {
+    client: &'a mut TestClient,
+    builder: request::Builder,
+    body: Body,
+}
+
+impl TestRequest<'_> {
+    /// Add a header to the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use direkuta::prelude::*;
+    /// # use direkuta::prelude::hyper::*;
+    /// # use direkuta::prelude::test::*;
+    /// let mut client = TestClient::new(Direkuta::new());
+    ///
+    /// let res = client.get("/")
+    ///     .header(header::ACCEPT, "text/plain")
+    ///     .send()
+    ///     .unwrap();
+    /// ```
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: HttpTryFrom<K>,
+        HeaderValue: HttpTryFrom<V>,
+    {
+        let _ = self.builder.header(key, value);
+        self
+    }
+
+    /// Set the body of the request.
+    pub fn body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Set the client certificate seen by `Request::peer_certificate`.
+    #[cfg(feature = "tls")]
+    pub fn peer_certificate(mut self, cert: PeerCertificate) -> Self {
+        let _ = self.builder.extension(Arc::new(cert));
+        self
+    }
+
+    /// Dispatch the request and wait for the whole response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request is invalid or if the handler or body returned an error.
+    pub fn send(mut self) -> Result<TestResponse, DireError> {
+        let req = self
+            .builder
+            .body(self.body)
+            .map_err(|e| DireError::BadRequest(e.to_string()))?;
+
+        let client = self.client;
+
+        let mut service = client.runtime.block_on(client.app.new_service())?;
+
+        let res = client.runtime.block_on(service.call(req))?;
+        let (parts, body) = res.into_parts();
+
+        let body = client.runtime.block_on(body.concat2())?;
+
+        Ok(TestResponse {
+            parts,
+            body: body.to_vec(),
+        })
+    }
+}
+
+/// A collected response from TestClient.
+pub struct TestResponse {
+    parts: response::Parts,
+    body: Vec<u8>,
+}
+
+impl TestResponse {
+    /// Return Response HTTP status code.
+    pub fn status(&self) -> StatusCode {
+        self.parts.status
+    }
+
+    /// Return Response HTTP headers.
+    pub fn headers(&self) -> &HeaderMap<HeaderValue> {
+        &self.parts.headers
+    }
+
+    /// Return Response body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Return Response body as a string, invalid UTF-8 is replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
 }
 
 /// Creates a HeaderMap from a list of key-value pairs.
@@ -1900,13 +5769,20 @@ macro_rules! headermap {
 
 /// Imports just the required parts of Direkuta.
 pub mod prelude {
-    pub use super::{Capture, DireError, Direkuta, Logger, Middle, Request, Response, State};
+    pub use super::{
+        Bound, Capture, CaptureValue, DireError, Direkuta, Logger, Middle, Request, Response,
+        State, TimedOut, Urls,
+    };
+    #[cfg(feature = "tls")]
+    pub use super::PeerCertificate;
+    #[cfg(feature = "html")]
+    pub use super::TemplateError;
 
     /// Imports all builders used in Direkuta.
     ///
     /// Useful for turing the closures into stand-alone functions.
     pub mod builder {
-        pub use super::super::{Config, CssBuilder, JsBuilder, Router};
+        pub use super::super::{Config, CssBuilder, JsBuilder, RouteBuilder, Router};
         #[cfg(feature = "json")]
         pub use super::super::JsonBuilder;
     }
@@ -1925,7 +5801,12 @@ pub mod prelude {
     /// or if you want to set response Headers.
     pub mod hyper {
         pub use hyper::header::{self, HeaderMap, HeaderValue};
-        pub use hyper::{Body, Method};
+        pub use hyper::{Body, Method, StatusCode};
+    }
+
+    /// Imports the in-process test client.
+    pub mod test {
+        pub use super::super::{TestClient, TestRequest, TestResponse};
     }
 
     /// Exports Futures' 'future', 'Future', and 'Stream'.
@@ -1940,7 +5821,93 @@ pub mod prelude {
         pub type Res = Response<Body>;
 
         /// Type alias for Router returns.
-        pub type FutureResponse = Box<Future<Item = Res, Error = DireError> + Send + 'static>;
+        pub type FutureResponse = Box<dyn Future<Item = Res, Error = DireError> + Send + 'static>;
         pub use futures::{future, Future, Stream};
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::{Activity, SkipContinue, CONTINUE};
+
+    /// Blocks on its first write, then takes a few bytes at a time.
+    #[derive(Default)]
+    struct Slow {
+        written: Vec<u8>,
+        blocked: bool,
+    }
+
+    impl Write for Slow {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.blocked {
+                self.blocked = true;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let n = buf.len().min(4);
+            self.written.extend_from_slice(&buf[..n]);
+
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Write the whole buffer like Hyper does, trying again when the IO blocks.
+    fn write(io: &mut SkipContinue<Slow>, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            match io.write(buf) {
+                Ok(n) => buf = &buf[n..],
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("Unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn skip_continue_across_blocked_and_short_writes() {
+        let activity = Arc::new(Activity::default());
+        let mut io = SkipContinue {
+            io: Slow::default(),
+            activity: activity.clone(),
+        };
+
+        let head = b"HTTP/1.1 413 Payload Too Large\r\ncontent-length: 0\r\n\r\n";
+        let mut buf = CONTINUE.to_vec();
+        buf.extend_from_slice(head);
+
+        activity.skip_continue.store(true, Ordering::SeqCst);
+        write(&mut io, &buf);
+
+        assert_eq!(io.io.written, &head[..]);
+        assert!(!activity.skip_continue.load(Ordering::SeqCst));
+
+        // Later responses are written as they are
+        write(&mut io, &buf);
+
+        assert_eq!(io.io.written[head.len()..], buf[..]);
+    }
+
+    #[test]
+    fn skip_continue_without_interim_response() {
+        let activity = Arc::new(Activity::default());
+        let mut io = SkipContinue {
+            io: Slow::default(),
+            activity: activity.clone(),
+        };
+
+        let head = b"HTTP/1.0 413 Payload Too Large\r\n\r\n";
+
+        activity.skip_continue.store(true, Ordering::SeqCst);
+        write(&mut io, head);
+
+        assert_eq!(io.io.written, &head[..]);
+        assert!(!activity.skip_continue.load(Ordering::SeqCst));
+    }
+}
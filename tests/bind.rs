@@ -0,0 +1,89 @@
+extern crate direkuta;
+extern crate futures;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use direkuta::prelude::*;
+use futures::sync::oneshot;
+use futures::Future;
+
+fn app() -> Direkuta {
+    Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+    })
+}
+
+fn get(bound: Bound) -> String {
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    request(addr)
+}
+
+fn request(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    response
+}
+
+#[test]
+fn bind_ephemeral_port() {
+    let bound = app().bind("127.0.0.1:0").expect("To bind server");
+
+    assert_ne!(bound.local_addr().unwrap().port(), 0);
+    assert!(get(bound).ends_with("Hello World!"));
+}
+
+#[test]
+fn bind_existing_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("To bind listener");
+    let addr = listener.local_addr().expect("To get address");
+
+    let bound = app().listen(listener).expect("To use listener");
+
+    assert_eq!(bound.local_addr(), Some(addr));
+    assert!(get(bound).ends_with("Hello World!"));
+}
+
+#[test]
+fn bind_multiple() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("To bind listener");
+
+    let bound = app()
+        .bind("127.0.0.1:0")
+        .expect("To bind server")
+        .listen(listener)
+        .expect("To use listener");
+
+    let addrs = bound.local_addrs();
+    assert_eq!(addrs.len(), 2);
+    assert_eq!(bound.local_addr(), Some(addrs[0]));
+
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = thread::spawn(move || {
+        bound.run_until(rx.map_err(|_| ())).expect("To run server");
+    });
+
+    for addr in &addrs {
+        assert!(request(*addr).ends_with("Hello World!"));
+    }
+
+    tx.send(()).expect("To send shutdown signal");
+    server.join().expect("To stop server");
+
+    for addr in &addrs {
+        assert!(TcpStream::connect(addr).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+extern crate direkuta;
+extern crate futures;
+extern crate hyper;
+
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use direkuta::prelude::hyper::*;
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+use futures::{Future, Stream};
+
+fn echo(body: &[u8]) -> hyper::Response<Body> {
+    Response::new()
+        .with_body(String::from_utf8_lossy(body).into_owned())
+        .into_hyper()
+}
+
+fn app(called: Arc<AtomicBool>) -> Direkuta {
+    Direkuta::config(|c| {
+        c.max_body_size(16);
+    }).route(move |r| {
+        let called = called.clone();
+        r.post("/", move |req, _, _| {
+            called.store(true, Ordering::SeqCst);
+
+            Box::new(
+                req.into_body()
+                    .concat2()
+                    .map_err(DireError::from)
+                    .map(|body| echo(&body)),
+            )
+        });
+        r.post("/large", |req, _, _| {
+            Box::new(
+                req.into_body()
+                    .concat2()
+                    .map_err(DireError::from)
+                    .map(|body| echo(&body)),
+            )
+        }).max_body_size(64);
+    })
+}
+
+#[test]
+fn body_within_limit() {
+    let mut client = TestClient::new(app(Arc::default()));
+
+    let res = client.post("/").body("Hello World!").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Hello World!");
+}
+
+#[test]
+fn body_streamed_over_limit() {
+    let mut client = TestClient::new(app(Arc::default()));
+
+    let res = client.post("/").body("a".repeat(32)).send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 413);
+}
+
+#[test]
+fn body_content_length_over_limit() {
+    let called = Arc::new(AtomicBool::new(false));
+    let mut client = TestClient::new(app(called.clone()));
+
+    let res = client
+        .post("/")
+        .header(header::CONTENT_LENGTH, "32")
+        .body("a".repeat(32))
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 413);
+    assert!(!called.load(Ordering::SeqCst));
+}
+
+#[test]
+fn body_route_override() {
+    let mut client = TestClient::new(app(Arc::default()));
+
+    let res = client.post("/large").body("a".repeat(32)).send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+
+    let res = client.post("/large").body("a".repeat(128)).send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 413);
+}
+
+#[test]
+fn body_error_handler() {
+    let mut client = TestClient::new(app(Arc::default()).error_handler(|e| match e {
+        DireError::BodyTooLarge(limit) => Response::new()
+            .with_status(413)
+            .with_body(format!("At most {} bytes", limit)),
+        _ => Response::new().with_status(500),
+    }));
+
+    let res = client
+        .post("/")
+        .header(header::CONTENT_LENGTH, "32")
+        .body("a".repeat(32))
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 413);
+    assert_eq!(res.text(), "At most 16 bytes");
+
+    let res = client.post("/large").body("a".repeat(128)).send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 413);
+    assert_eq!(res.text(), "At most 64 bytes");
+}
+
+#[test]
+fn body_expect_continue() {
+    let bound = app(Arc::default()).bind("127.0.0.1:0").expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("To set timeout");
+    stream
+        .write_all(
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1048576\r\nExpect: 100-continue\r\n\r\n",
+        ).expect("To write request");
+
+    // The body is never sent, the server has to answer from the headers alone.
+    let mut res = String::new();
+    let mut buf = [0; 1024];
+
+    while !res.contains("\r\n\r\n") {
+        let n = stream.read(&mut buf).expect("To read response");
+        assert!(n > 0, "Connection closed before a response: {:?}", res);
+
+        res.push_str(&String::from_utf8_lossy(&buf[..n]));
+    }
+
+    assert!(res.starts_with("HTTP/1.1 413"), "Unexpected response: {:?}", res);
+    assert!(!res.contains("100 Continue"));
+}
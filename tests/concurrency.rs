@@ -0,0 +1,94 @@
+extern crate direkuta;
+extern crate futures;
+extern crate hyper;
+extern crate tokio;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::*;
+use futures::Future;
+use tokio::timer::Delay;
+
+fn sleep(
+    delay: Duration,
+) -> Box<dyn Future<Item = hyper::Response<hyper::Body>, Error = DireError> + Send> {
+    Box::new(
+        Delay::new(Instant::now() + delay)
+            .map_err(|e| DireError::Other(e.to_string()))
+            .map(|_| Response::new().with_body("Done").into_hyper()),
+    )
+}
+
+fn start(app: Direkuta) -> SocketAddr {
+    let bound = app.bind("127.0.0.1:0").expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    addr
+}
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    ).expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    response
+}
+
+/// Start a request in the background and give it time to reach its handler.
+fn hold(addr: SocketAddr, path: &'static str) -> thread::JoinHandle<String> {
+    let held = thread::spawn(move || get(addr, path));
+    thread::sleep(Duration::from_millis(200));
+    held
+}
+
+#[test]
+fn concurrency_global_limit() {
+    let addr = start(Direkuta::config(|c| {
+        c.max_concurrency(1);
+        c.retry_after(Duration::from_millis(1500));
+    }).route(|r| {
+        r.get("/slow", |_, _, _| sleep(Duration::from_secs(1)));
+        r.get("/fast", |_, _, _| sleep(Duration::from_millis(0)));
+    }));
+
+    let held = hold(addr, "/slow");
+
+    let res = get(addr, "/fast");
+    assert!(res.starts_with("HTTP/1.1 503"));
+    assert!(res.to_lowercase().contains("retry-after: 2\r\n"));
+
+    assert!(held.join().unwrap().starts_with("HTTP/1.1 200"));
+    assert!(get(addr, "/fast").starts_with("HTTP/1.1 200"));
+}
+
+#[test]
+fn concurrency_route_limit() {
+    let addr = start(Direkuta::new().route(|r| {
+        r.get("/slow", |_, _, _| sleep(Duration::from_secs(1)))
+            .max_concurrency(1);
+        r.get("/fast", |_, _, _| sleep(Duration::from_millis(0)));
+    }));
+
+    let held = hold(addr, "/slow");
+
+    let res = get(addr, "/slow");
+    assert!(res.starts_with("HTTP/1.1 503"));
+    assert!(res.to_lowercase().contains("retry-after: 1\r\n"));
+
+    assert!(get(addr, "/fast").starts_with("HTTP/1.1 200"));
+
+    assert!(held.join().unwrap().starts_with("HTTP/1.1 200"));
+    assert!(get(addr, "/slow").starts_with("HTTP/1.1 200"));
+}
@@ -0,0 +1,117 @@
+extern crate direkuta;
+extern crate futures;
+extern crate hyper;
+
+use std::error::Error;
+use std::io;
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+use futures::future;
+use hyper::StatusCode;
+
+fn app() -> Direkuta {
+    Direkuta::new().route(|r| {
+        r.get("/missing", |_, _, _| {
+            Box::new(future::err(DireError::Status(
+                StatusCode::NOT_FOUND,
+                "No such thing".to_string(),
+            )))
+        });
+        r.get("/broken", |_, _, _| {
+            Box::new(future::err(DireError::Other("Database is down".to_string())))
+        });
+        r.get("/number/<id:(.+)>", |_, _, c| match c.parse::<u32>("id") {
+            Ok(id) => Response::new().with_body(id.to_string()).build(),
+            Err(e) => Box::new(future::err(e)),
+        });
+    })
+}
+
+#[test]
+fn error_status() {
+    let res = TestClient::new(app())
+        .get("/missing")
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+    assert_eq!(res.text(), "No such thing");
+}
+
+#[test]
+fn error_default() {
+    let res = TestClient::new(app())
+        .get("/broken")
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 500);
+    assert_eq!(res.text(), "Internal Server Error");
+}
+
+#[test]
+fn error_handler_custom() {
+    let app = app().error_handler(|e| match e {
+        DireError::Other(message) => Response::new()
+            .with_status(503)
+            .with_body(format!("Sorry: {}", message)),
+        _ => Response::new().with_status(500),
+    });
+
+    let res = TestClient::new(app)
+        .get("/broken")
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 503);
+    assert_eq!(res.text(), "Sorry: Database is down");
+}
+
+#[test]
+fn error_param() {
+    let mut client = TestClient::new(app());
+
+    let res = client.get("/number/5").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "5");
+
+    let res = client.get("/number/five").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 400);
+    assert_eq!(res.text(), "Bad Request");
+}
+
+#[test]
+fn error_source() {
+    let err = DireError::from(io::Error::new(io::ErrorKind::PermissionDenied, "Disk is read only"));
+
+    assert_eq!(err.status().as_u16(), 500);
+    assert_eq!(err.source().unwrap().to_string(), "Disk is read only");
+
+    let err = "five".parse::<u32>().map_err(|e| DireError::Param("id".to_string(), Box::new(e)));
+    let err = err.unwrap_err();
+
+    assert_eq!(err.status().as_u16(), 400);
+    assert!(err.source().is_some());
+}
+
+#[cfg(feature = "html")]
+#[test]
+fn error_template_source() {
+    use direkuta::prelude::html::{Context, Tera};
+
+    let tera_err = Tera::one_off("{{ missing }}", &Context::new(), false).unwrap_err();
+    let messages: Vec<String> = tera_err.iter().map(|e| e.to_string()).collect();
+
+    let err = DireError::from(tera_err);
+    let mut chain = Vec::new();
+    let mut source = err.source();
+
+    while let Some(e) = source {
+        chain.push(e.to_string());
+        source = e.source();
+    }
+
+    assert!(messages.len() > 1);
+    assert_eq!(chain, messages);
+}
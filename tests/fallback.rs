@@ -0,0 +1,79 @@
+extern crate direkuta;
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+fn client() -> TestClient {
+    TestClient::new(Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| Response::new().with_body("Home").build());
+        r.fallback(|req, _| {
+            Response::new()
+                .with_status(404)
+                .with_body(format!("No page at {}", req.path()))
+                .build()
+        });
+        r.path("/api", |r| {
+            r.get("/users", |_, _, _| Response::new().with_body("Users").build());
+            r.fallback(|_, _| {
+                Response::new()
+                    .with_status(404)
+                    .with_body("Unknown endpoint")
+                    .build()
+            });
+        });
+        r.path("/<name:([a-z]+)>/files", |r| {
+            r.fallback(|_, _| Response::new().with_status(404).build());
+        });
+    }))
+}
+
+#[test]
+fn fallback_global() {
+    let res = client().get("/missing").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+    assert_eq!(res.text(), "No page at /missing");
+}
+
+#[test]
+fn fallback_scoped() {
+    let mut client = client();
+
+    let res = client.get("/api/missing").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 404);
+    assert_eq!(res.text(), "Unknown endpoint");
+
+    let res = client.get("/api").send().expect("To send request");
+    assert_eq!(res.text(), "Unknown endpoint");
+
+    let res = client.get("/apis").send().expect("To send request");
+    assert_eq!(res.text(), "No page at /apis");
+}
+
+#[test]
+fn fallback_routes_first() {
+    let mut client = client();
+
+    let res = client.get("/").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Home");
+
+    let res = client.get("/api/users").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Users");
+}
+
+#[test]
+fn fallback_other_methods() {
+    let res = client().post("/api/users").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 405);
+}
+
+#[test]
+fn fallback_pattern_scope() {
+    let res = client().get("/txuritan/files/a").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+    assert!(res.body().is_empty());
+}
@@ -1,43 +1,66 @@
 extern crate direkuta;
-extern crate tokio;
-extern crate yukikaze;
 
-use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
+use direkuta::prelude::hyper::*;
+use direkuta::prelude::test::*;
 use direkuta::prelude::*;
-use yukikaze::client::{Client, HttpClient, Request};
-
-fn server() {
-    Direkuta::new()
-        .route(|r| {
-            r.get("/", |_, _, _| {
-                Response::new().with_body("Hello World!").build()
-            });
-        }).run("0.0.0.0:3000");
+
+fn client() -> TestClient {
+    TestClient::new(Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+        r.get("/<name:([a-z]+)>", |_, _, c| {
+            Response::new().with_body(c.get("name")).build()
+        });
+    }))
 }
 
 #[test]
 fn get_pass() {
-    thread::spawn(move || {
-        server();
-    });
+    let res = client().get("/").send().expect("To send request");
 
-    let mut tokio_rt = tokio::runtime::current_thread::Runtime::new().expect("To create runtime");
-    let client = Client::default();
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.headers()[header::CONTENT_LENGTH], "12");
+    assert_eq!(res.text(), "Hello World!");
+}
 
-    let request = Request::get("http://localhost:3000")
-        .expect("To create get request")
-        .empty();
+#[test]
+fn get_capture() {
+    let res = client().get("/txuritan").send().expect("To send request");
 
-    let response = client.execute(request);
-    let response = tokio_rt.block_on(response);
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "txuritan");
+}
 
-    if let Ok(res) = response {
-        assert!(res.is_success());
-        assert_eq!(res.content_len().unwrap(), 12_u64);
+#[test]
+fn get_not_found() {
+    let res = client().get("/0/1").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+    assert!(res.body().is_empty());
+}
 
-        let body = res.text();
-        let result = tokio_rt.block_on(body);
-        assert_eq!(result.unwrap(), "Hello World!");
+#[test]
+fn get_middleware() {
+    struct Count(Arc<AtomicUsize>);
+
+    impl Middle for Count {
+        fn run(&self, _req: &mut Request) {
+            let _ = self.0.fetch_add(1, Ordering::SeqCst);
+        }
     }
+
+    let count = Arc::new(AtomicUsize::new(0));
+
+    let mut client = TestClient::new(Direkuta::new().middle(Count(count.clone())).route(|r| {
+        r.get("/", |_, _, _| Response::new().with_body("Hello World!").build());
+    }));
+
+    let res = client.get("/").send().expect("To send request");
+
+    assert_eq!(res.text(), "Hello World!");
+    assert_eq!(count.load(Ordering::SeqCst), 1);
 }
@@ -0,0 +1,81 @@
+extern crate direkuta;
+
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::thread;
+
+use direkuta::prelude::hyper::*;
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+fn app() -> Direkuta {
+    Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| Response::new().with_body("Hello World!").build());
+        r.post("/", |_, _, _| Response::new().with_body("Posted").build());
+        r.get("/custom", |_, _, _| Response::new().with_body("Body").build());
+        r.head("/custom", |_, _, _| Response::new().with_status(202).build());
+        r.options("/custom", |_, _, _| Response::new().with_body("Options").build());
+    })
+}
+
+#[test]
+fn head_runs_get() {
+    let res = TestClient::new(app()).head("/").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.headers()[header::CONTENT_LENGTH], "12");
+    assert!(res.body().is_empty());
+}
+
+#[test]
+fn head_explicit() {
+    let res = TestClient::new(app()).head("/custom").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 202);
+}
+
+#[test]
+fn head_over_the_wire() {
+    let bound = app().bind("127.0.0.1:0").expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .write_all(b"HEAD / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.to_lowercase().contains("content-length: 12\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
+
+#[test]
+fn options_lists_methods() {
+    let res = TestClient::new(app()).options("/").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 204);
+    assert_eq!(res.headers()[header::ALLOW], "GET, POST, HEAD, OPTIONS");
+    assert!(res.body().is_empty());
+}
+
+#[test]
+fn options_explicit() {
+    let res = TestClient::new(app()).options("/custom").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Options");
+}
+
+#[test]
+fn options_not_found() {
+    let res = TestClient::new(app()).options("/missing").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+}
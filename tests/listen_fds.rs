@@ -0,0 +1,109 @@
+#![cfg(unix)]
+
+extern crate direkuta;
+extern crate libc;
+
+use std::env;
+use std::mem;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::IntoRawFd;
+use std::process;
+use std::thread;
+
+use direkuta::prelude::*;
+
+fn app() -> Direkuta {
+    Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+    })
+}
+
+// Passed sockets always start at fd 3, so this is the only test in this binary.
+#[test]
+fn listen_fds() {
+    match app().listen_fds() {
+        Err(DireError::Bind(_)) => {}
+        Err(e) => panic!("Expected bind error, got {}", e),
+        Ok(_) => panic!("Expected bind error"),
+    }
+
+    // A count running past the last fd is rejected rather than wrapping around.
+    for count in &["4294967295", "2147483647", "-1"] {
+        env::set_var("LISTEN_PID", process::id().to_string());
+        env::set_var("LISTEN_FDS", count);
+
+        match app().listen_fds() {
+            Err(DireError::Config(_)) => {}
+            Err(e) => panic!("Expected config error, got {}", e),
+            Ok(_) => panic!("Expected config error"),
+        }
+
+        assert!(env::var("LISTEN_FDS").is_err());
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("To bind listener");
+    let addr = listener.local_addr().expect("To get address");
+
+    // The listener may already be fd 3 if nothing else is open.
+    let fd = listener.into_raw_fd();
+    if fd != 3 {
+        unsafe {
+            assert_eq!(libc::dup2(fd, 3), 3);
+            assert_eq!(libc::close(fd), 0);
+        }
+    }
+
+    // Like the service manager, pass it without close-on-exec.
+    unsafe {
+        assert_eq!(libc::fcntl(3, libc::F_SETFD, 0), 0);
+    }
+
+    env::set_var("LISTEN_PID", process::id().to_string());
+    env::set_var("LISTEN_FDS", "1");
+
+    let bound = app().listen_fds().expect("To use passed socket");
+
+    assert_eq!(bound.local_addr(), Some(addr));
+    assert!(env::var("LISTEN_FDS").is_err());
+
+    // The socket must not leak in to child processes.
+    let mut found = false;
+    for fd in 0..1024 {
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFD);
+            if flags == -1 {
+                continue;
+            }
+
+            let mut name: libc::sockaddr_in = mem::zeroed();
+            let mut len = mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+            let is_socket = libc::getsockname(fd, &mut name as *mut _ as *mut libc::sockaddr, &mut len) == 0;
+
+            if is_socket
+                && i32::from(name.sin_family) == libc::AF_INET
+                && u16::from_be(name.sin_port) == addr.port()
+            {
+                assert_ne!(flags & libc::FD_CLOEXEC, 0, "fd {} is inherited by children", fd);
+                found = true;
+            }
+        }
+    }
+    assert!(found);
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.ends_with("Hello World!"));
+}
@@ -0,0 +1,39 @@
+extern crate direkuta;
+
+use direkuta::prelude::hyper::*;
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+fn client() -> TestClient {
+    TestClient::new(Direkuta::new().route(|r| {
+        r.post("/users", |_, _, _| Response::new().with_body("Created").build());
+        r.delete("/users/<id:([0-9]+)>", |_, _, _| Response::new().build());
+        r.path("/users/<id:([0-9]+)>", |r| {
+            r.put("", |_, _, _| Response::new().build());
+        });
+    }))
+}
+
+#[test]
+fn method_not_allowed() {
+    let res = client().get("/users").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 405);
+    assert_eq!(res.headers()[header::ALLOW], "POST, OPTIONS");
+}
+
+#[test]
+fn method_not_allowed_lists_all() {
+    let res = client().get("/users/5").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 405);
+    assert_eq!(res.headers()[header::ALLOW], "DELETE, PUT, OPTIONS");
+}
+
+#[test]
+fn method_not_allowed_unknown_path() {
+    let res = client().get("/users/five").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 404);
+    assert!(res.headers().get(header::ALLOW).is_none());
+}
@@ -0,0 +1,106 @@
+extern crate direkuta;
+extern crate futures;
+extern crate hyper;
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+use futures::future;
+
+/// Panics before the handler for paths starting with `/middle`.
+struct Panicky;
+
+impl Middle for Panicky {
+    fn run(&self, req: &mut Request) {
+        if req.uri().path().starts_with("/middle") {
+            panic!("middleware broke");
+        }
+    }
+
+    fn after(&self, res: &mut hyper::Response<hyper::Body>) {
+        if res.status().as_u16() == 418 {
+            panic!("after hook broke");
+        }
+    }
+}
+
+fn app() -> Direkuta {
+    Direkuta::new().middle(Panicky).route(|r| {
+        r.get("/handler", |_, _, c| {
+            let id = c.get_parse::<u32>("id");
+            Response::new().with_body(id.to_string()).build()
+        });
+        r.get("/future", |_, _, _| {
+            Box::new(future::lazy(|| -> Result<hyper::Response<hyper::Body>, DireError> {
+                panic!("future broke")
+            }))
+        });
+        r.get("/middle", |_, _, _| Response::new().with_body("Unreachable").build());
+        r.get("/teapot", |_, _, _| Response::new().with_status(418).build());
+        r.get("/fine", |_, _, _| Response::new().with_body("Fine").build());
+    })
+}
+
+fn status(app: Direkuta, path: &str) -> u16 {
+    TestClient::new(app)
+        .get(path)
+        .send()
+        .expect("To send request")
+        .status()
+        .as_u16()
+}
+
+#[test]
+fn panic_in_handler() {
+    assert_eq!(status(app(), "/handler"), 500);
+}
+
+#[test]
+fn panic_in_future() {
+    assert_eq!(status(app(), "/future"), 500);
+}
+
+#[test]
+fn panic_in_middleware() {
+    assert_eq!(status(app(), "/middle"), 500);
+    assert_eq!(status(app(), "/teapot"), 500);
+}
+
+#[test]
+fn panic_error_handler() {
+    let app = app().error_handler(|e| match e {
+        DireError::Panic(message) => Response::new().with_status(503).with_body(message),
+        _ => Response::new().with_status(500),
+    });
+
+    let res = TestClient::new(app)
+        .get("/future")
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 503);
+    assert_eq!(res.text(), "future broke");
+}
+
+#[test]
+fn panic_in_error_handler() {
+    let mut client = TestClient::new(app().error_handler(|_| panic!("error handler broke")));
+
+    let res = client.get("/future").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 500);
+    assert_eq!(res.text(), "");
+
+    let res = client.get("/fine").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+}
+
+#[test]
+fn panic_does_not_affect_other_requests() {
+    let mut client = TestClient::new(app());
+
+    let res = client.get("/handler").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 500);
+
+    let res = client.get("/fine").send().expect("To send request");
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Fine");
+}
@@ -0,0 +1,123 @@
+extern crate direkuta;
+extern crate futures;
+extern crate hyper;
+extern crate tokio;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+use futures::Future;
+use tokio::timer::Delay;
+
+/// Records the timeouts seen by middleware.
+struct Recorder(Arc<Mutex<Vec<Duration>>>);
+
+impl Middle for Recorder {
+    fn run(&self, _: &mut Request) {}
+
+    fn after(&self, res: &mut hyper::Response<hyper::Body>) {
+        if let Some(timed_out) = res.extensions().get::<TimedOut>() {
+            self.0.lock().unwrap().push(timed_out.timeout());
+        }
+    }
+}
+
+fn sleep(
+    delay: Duration,
+) -> Box<dyn Future<Item = hyper::Response<hyper::Body>, Error = DireError> + Send> {
+    Box::new(
+        Delay::new(Instant::now() + delay)
+            .map_err(|e| DireError::Other(e.to_string()))
+            .map(|_| Response::new().with_body("Done").into_hyper()),
+    )
+}
+
+fn client(config: impl Fn(&mut builder::Config) + Send + Sync + 'static) -> TestClient {
+    TestClient::new(Direkuta::config(move |c| {
+        c.request_timeout(Duration::from_millis(100));
+        config(c);
+    }).route(|r| {
+        r.get("/fast", |_, _, _| sleep(Duration::from_millis(0)));
+        r.get("/slow", |_, _, _| sleep(Duration::from_secs(10)));
+        r.get("/override", |_, _, _| sleep(Duration::from_millis(200)))
+            .timeout(Duration::from_secs(5));
+    }))
+}
+
+#[test]
+fn request_timeout_fast() {
+    let res = client(|_| {}).get("/fast").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 200);
+    assert_eq!(res.text(), "Done");
+}
+
+#[test]
+fn request_timeout_slow() {
+    let start = Instant::now();
+    let res = client(|_| {}).get("/slow").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 504);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn request_timeout_status() {
+    let res = client(|c| c.timeout_status(hyper::StatusCode::SERVICE_UNAVAILABLE))
+        .get("/slow")
+        .send()
+        .expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 503);
+}
+
+#[test]
+fn request_timeout_route_override() {
+    let res = client(|_| {}).get("/override").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 200);
+}
+
+#[test]
+fn request_timeout_path_scope() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let mut client = TestClient::new(Direkuta::new()
+        .middle(Recorder(seen.clone()))
+        .route(|r| {
+            r.path("/scope", |r| {
+                r.get("/short", |_, _, _| sleep(Duration::from_secs(10)))
+                    .timeout(Duration::from_millis(50));
+            });
+        }));
+
+    let res = client.get("/scope/short").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 504);
+    assert_eq!(*seen.lock().unwrap(), vec![Duration::from_millis(50)]);
+}
+
+#[test]
+fn request_timeout_error_handler() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let mut client = TestClient::new(Direkuta::config(|c| {
+        c.request_timeout(Duration::from_millis(50));
+    }).middle(Recorder(seen.clone()))
+    .error_handler(|e| match e {
+        DireError::Timeout(timeout) => Response::new()
+            .with_status(503)
+            .with_body(format!("Gave up after {:?}", timeout)),
+        _ => Response::new().with_status(500),
+    }).route(|r| {
+        r.get("/slow", |_, _, _| sleep(Duration::from_secs(10)));
+    }));
+
+    let res = client.get("/slow").send().expect("To send request");
+
+    assert_eq!(res.status().as_u16(), 503);
+    assert_eq!(res.text(), "Gave up after 50ms");
+    assert_eq!(*seen.lock().unwrap(), vec![Duration::from_millis(50)]);
+}
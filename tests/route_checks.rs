@@ -0,0 +1,172 @@
+extern crate direkuta;
+
+use direkuta::prelude::builder::Router;
+use direkuta::prelude::*;
+
+fn check(route: impl Fn(&mut Router) + Send + Sync + 'static) -> Result<(), String> {
+    match Direkuta::new().route(route).build() {
+        Ok(_) => Ok(()),
+        Err(DireError::Route(path, message)) => Err(format!("`{}` {}", path, message)),
+        Err(e) => panic!("Unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn valid_routes_build() {
+    let result = check(|r| {
+        r.get("/users/<id>", |_, _, _| Response::new().build());
+        r.post("/users/<id>", |_, _, _| Response::new().build());
+        r.get("/users/new", |_, _, _| Response::new().build());
+        r.get("/files/<path:(.+)>", |_, _, _| Response::new().build());
+        r.get("/<slug:([a-z]+(?:-[a-z]+)*)>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn duplicate_routes_are_unreachable() {
+    let result = check(|r| {
+        r.get("/users/<id>", |_, _, _| Response::new().build());
+        r.get("/users/<name>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(
+        result,
+        Err("`/users/<name>` is unreachable, the GET route `/users/<id>` matches the same paths"
+            .to_string())
+    );
+}
+
+#[test]
+fn plain_routes_shadowed_by_the_same_pattern_are_unreachable() {
+    let result = check(|r| {
+        r.get("/users/<id:[^/]+>", |_, _, _| Response::new().build());
+        r.get("/users/<id>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(
+        result,
+        Err("`/users/<id>` is unreachable, the GET route `/users/<id:[^/]+>` matches the same paths"
+            .to_string())
+    );
+}
+
+#[test]
+fn scoped_duplicates_are_unreachable() {
+    let result = check(|r| {
+        r.get("/teams/list", |_, _, _| Response::new().build());
+        r.path("/teams", |r| {
+            r.get("/list", |_, _, _| Response::new().build());
+        });
+    });
+
+    assert_eq!(
+        result,
+        Err("`/teams/list` is unreachable, the GET route `/teams/list` matches the same paths"
+            .to_string())
+    );
+}
+
+#[test]
+fn duplicate_ids_are_rejected() {
+    let result = check(|r| {
+        r.path("/teams/<id>", |r| {
+            r.get("/members/<id>", |_, _, _| Response::new().build());
+        });
+    });
+
+    assert_eq!(
+        result,
+        Err("`/teams/<id>/members/<id>` uses the id `id` twice".to_string())
+    );
+}
+
+#[test]
+fn duplicate_fallbacks_are_unreachable() {
+    let result = check(|r| {
+        r.fallback(|_, _| Response::new().build());
+        r.fallback(|_, _| Response::new().build());
+    });
+
+    assert!(result.unwrap_err().contains("is unreachable, the fallback"));
+}
+
+#[test]
+fn every_error_is_reported() {
+    let result = Direkuta::new()
+        .route(|r| {
+            r.get("/a", |_, _, _| Response::new().build());
+            r.get("/a", |_, _, _| Response::new().build());
+            r.get("/<id:(>", |_, _, _| Response::new().build());
+        })
+        .build();
+
+    match result {
+        Err(DireError::Routes(errors)) => {
+            assert_eq!(errors.len(), 2);
+            assert!(matches!(errors[0], DireError::Pattern(ref path, _) if path == "/<id:(>"));
+            assert!(matches!(errors[1], DireError::Route(ref path, _) if path == "/a"));
+        }
+        _ => panic!("Expected both errors to be reported"),
+    }
+}
+
+#[test]
+fn narrower_segments_before_broad_patterns_build() {
+    let result = check(|r| {
+        r.get("/<a:.*>", |_, _, _| Response::new().build());
+        r.get("/<b:[0-9]+>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn params_tried_first_shadow_typed_ones() {
+    let result = check(|r| {
+        r.get("/posts/<slug>", |_, _, _| Response::new().build());
+        r.get("/posts/<id:int>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(
+        result,
+        Err("`/posts/<id:int>` is unreachable, the GET route `/posts/<slug>` is tried first and matches all of its paths"
+            .to_string())
+    );
+}
+
+#[test]
+fn typed_params_before_plain_ones_build() {
+    let result = check(|r| {
+        r.get("/posts/<id:int>", |_, _, _| Response::new().build());
+        r.get("/posts/<slug>", |_, _, _| Response::new().build());
+        r.get("/<name>/edit", |_, _, _| Response::new().build());
+        r.get("/users/edit", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn types_are_compared_with_patterns() {
+    let result = check(|r| {
+        r.get("/<a:int>", |_, _, _| Response::new().build());
+        r.get("/<a:-?[0-9]+>", |_, _, _| Response::new().build());
+        r.get("/files/<a:int>/<rest:path>", |_, _, _| Response::new().build());
+        r.get("/files/<a:-?[0-9]+>/<rest:path>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(result, Ok(()));
+
+    let result = check(|r| {
+        r.get("/files/<a:-?[0-9]+>/<rest:path>", |_, _, _| Response::new().build());
+        r.get("/files/<a:int>/<rest:path>", |_, _, _| Response::new().build());
+    });
+
+    assert_eq!(
+        result,
+        Err("`/files/<a:int>/<rest:path>` is unreachable, the GET route `/files/<a:-?[0-9]+>/<rest:path>` matches the same paths"
+            .to_string())
+    );
+}
@@ -0,0 +1,285 @@
+extern crate direkuta;
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+fn client() -> TestClient {
+    TestClient::new(Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| Response::new().with_body("Home").build());
+        r.get("/users/<id>", |_, _, c| {
+            Response::new().with_body(format!("User {}", c.get("id"))).build()
+        });
+        r.get("/users/new", |_, _, _| Response::new().with_body("New user").build());
+        r.get("/users/<id>/posts/<post>", |_, _, c| {
+            Response::new()
+                .with_body(format!("Post {} by {}", c.get("post"), c.get("id")))
+                .build()
+        });
+        r.get("/files/<path:(.+)>", |_, _, c| {
+            Response::new().with_body(c.get("path")).build()
+        });
+        r.get("/<slug:[a-z]+>-page", |_, _, c| {
+            Response::new().with_body(c.get("slug")).build()
+        });
+        r.path("/teams/<team>", |r| {
+            r.get("", |_, _, c| Response::new().with_body(c.get("team")).build());
+            r.get("/members/<id>", |_, _, c| {
+                Response::new()
+                    .with_body(format!("{} of {}", c.get("id"), c.get("team")))
+                    .build()
+            });
+        });
+    }))
+}
+
+fn get(client: &mut TestClient, path: &str) -> (u16, String) {
+    let res = client.get(path).send().expect("To send request");
+
+    (res.status().as_u16(), res.text())
+}
+
+#[test]
+fn router_static_and_params() {
+    let mut client = client();
+
+    assert_eq!(get(&mut client, "/"), (200, "Home".to_string()));
+    assert_eq!(get(&mut client, "/users/5"), (200, "User 5".to_string()));
+    assert_eq!(get(&mut client, "/users/5/"), (200, "User 5".to_string()));
+    assert_eq!(
+        get(&mut client, "/users/5/posts/9"),
+        (200, "Post 9 by 5".to_string())
+    );
+}
+
+#[test]
+fn router_static_before_param() {
+    let mut client = client();
+
+    assert_eq!(get(&mut client, "/users/new"), (200, "New user".to_string()));
+}
+
+#[test]
+fn router_param_needs_a_segment() {
+    let mut client = client();
+
+    assert_eq!(get(&mut client, "/users/").0, 404);
+    assert_eq!(get(&mut client, "/users//posts/9").0, 404);
+    assert_eq!(get(&mut client, "/users/5/posts").0, 404);
+}
+
+#[test]
+fn router_custom_patterns() {
+    let mut client = client();
+
+    assert_eq!(get(&mut client, "/files/a/b.txt"), (200, "a/b.txt".to_string()));
+    assert_eq!(get(&mut client, "/about-page"), (200, "about".to_string()));
+}
+
+#[test]
+fn router_path_params() {
+    let mut client = client();
+
+    assert_eq!(get(&mut client, "/teams/red"), (200, "red".to_string()));
+    assert_eq!(
+        get(&mut client, "/teams/red/members/3"),
+        (200, "3 of red".to_string())
+    );
+}
+
+#[test]
+fn router_patterns_in_order() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<first:(x.*)>", |_, _, _| Response::new().with_body("first").build());
+        r.get("/<second:(.*)>", |_, _, _| Response::new().with_body("second").build());
+        r.get("/<third:(xy.*)>", |_, _, _| Response::new().with_body("third").build());
+    }));
+
+    assert_eq!(get(&mut client, "/xyz"), (200, "first".to_string()));
+    assert_eq!(get(&mut client, "/abc"), (200, "second".to_string()));
+}
+
+fn typed() -> TestClient {
+    TestClient::new(Direkuta::new().route(|r| {
+        r.get("/posts/<id:int>", |_, _, c| {
+            let body = match c.value("id") {
+                Some(CaptureValue::Int(id)) => format!("int {}", id),
+                other => format!("{:?}", other),
+            };
+
+            Response::new().with_body(body).build()
+        });
+        r.get("/tags/<tag:slug>", |_, _, c| {
+            Response::new().with_body(format!("{:?}", c.value("tag"))).build()
+        });
+        r.get("/items/<id:uuid>", |_, _, c| {
+            let body = match c.value("id") {
+                Some(CaptureValue::Uuid(id)) => format!("{:x}", id),
+                other => format!("{:?}", other),
+            };
+
+            Response::new().with_body(body).build()
+        });
+        r.get("/static/<rest:path>", |_, _, c| {
+            Response::new().with_body(format!("{:?}", c.value("rest"))).build()
+        });
+        r.get("/users/<name>", |_, _, c| {
+            Response::new().with_body(format!("{:?}", c.value("name"))).build()
+        });
+    }))
+}
+
+#[test]
+fn router_typed_params() {
+    let mut client = typed();
+
+    assert_eq!(get(&mut client, "/posts/-42"), (200, "int -42".to_string()));
+    assert_eq!(
+        get(&mut client, "/tags/rust-lang"),
+        (200, "Some(Slug(\"rust-lang\"))".to_string())
+    );
+    assert_eq!(
+        get(&mut client, "/items/123e4567-e89b-12d3-a456-426614174000"),
+        (200, "123e4567e89b12d3a456426614174000".to_string())
+    );
+    assert_eq!(
+        get(&mut client, "/static/css/site.css"),
+        (200, "Some(Path(\"css/site.css\"))".to_string())
+    );
+    assert_eq!(
+        get(&mut client, "/users/txuritan"),
+        (200, "Some(Str(\"txuritan\"))".to_string())
+    );
+}
+
+#[test]
+fn router_typed_params_reject() {
+    let mut client = typed();
+
+    assert_eq!(get(&mut client, "/posts/four").0, 404);
+    assert_eq!(get(&mut client, "/posts/99999999999999999999").0, 404);
+    assert_eq!(get(&mut client, "/tags/Not_A_Slug").0, 404);
+    assert_eq!(get(&mut client, "/items/123").0, 404);
+    assert_eq!(get(&mut client, "/static/").0, 404);
+}
+
+#[test]
+fn router_nested_groups() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<name:(a|(b))>/<id>", |_, _, c| {
+            Response::new()
+                .with_body(format!("{} {}", c.get("name"), c.get("id")))
+                .build()
+        });
+        r.get("/dates/<date:([0-9]{4})-([0-9]{2})>/<day:[0-9]{2}>", |_, _, c| {
+            Response::new()
+                .with_body(format!("{} {}", c.get("date"), c.get("day")))
+                .build()
+        });
+        r.get("/archive(?:/<year:int>)?", |_, _, c| {
+            Response::new().with_body(format!("{:?}", c.value("year"))).build()
+        });
+    }));
+
+    assert_eq!(get(&mut client, "/b/5"), (200, "b 5".to_string()));
+    assert_eq!(get(&mut client, "/dates/2018-07/21"), (200, "2018-07 21".to_string()));
+    assert_eq!(get(&mut client, "/archive/2018"), (200, "Some(Int(2018))".to_string()));
+    assert_eq!(get(&mut client, "/archive"), (200, "None".to_string()));
+}
+
+#[test]
+fn router_ids_with_hyphens() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/users/<user-id>", |_, _, c| {
+            Response::new().with_body(c.get("user-id")).build()
+        });
+        r.get("/posts/<post-id:[0-9]+>", |_, _, c| {
+            Response::new().with_body(c.get("post-id")).build()
+        });
+    }));
+
+    assert_eq!(get(&mut client, "/users/5"), (200, "5".to_string()));
+    assert_eq!(get(&mut client, "/posts/7"), (200, "7".to_string()));
+}
+
+#[test]
+fn router_pattern_segments() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<section>/<id>", |_, _, c| {
+            Response::new()
+                .with_body(format!("plain {} {}", c.get("section"), c.get("id")))
+                .build()
+        });
+        r.get("/api/<id:int>", |_, _, c| {
+            Response::new().with_body(format!("typed {}", c.get("id"))).build()
+        });
+        r.get("/api/<name:[a-z]+>-list", |_, _, c| {
+            Response::new().with_body(format!("regex {}", c.get("name"))).build()
+        });
+    }));
+
+    assert_eq!(get(&mut client, "/api/5"), (200, "typed 5".to_string()));
+    assert_eq!(get(&mut client, "/api/users-list"), (200, "regex users".to_string()));
+    assert_eq!(get(&mut client, "/api/x-y"), (200, "plain api x-y".to_string()));
+    assert_eq!(get(&mut client, "/docs/5"), (200, "plain docs 5".to_string()));
+}
+
+#[test]
+fn router_typed_params_fall_through() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<id:int>", |_, _, _| Response::new().with_body("int").build());
+        r.get("/<name:[0-9a-z]+>", |_, _, _| Response::new().with_body("name").build());
+        r.get("/files/<id:int>/<rest:path>", |_, _, _| {
+            Response::new().with_body("int file").build()
+        });
+        r.get("/files/<name:[0-9a-z]+>/<rest:path>", |_, _, _| {
+            Response::new().with_body("named file").build()
+        });
+    }));
+
+    assert_eq!(get(&mut client, "/42"), (200, "int".to_string()));
+    assert_eq!(
+        get(&mut client, "/99999999999999999999999"),
+        (200, "name".to_string())
+    );
+    assert_eq!(get(&mut client, "/files/42/a/b"), (200, "int file".to_string()));
+    assert_eq!(
+        get(&mut client, "/files/99999999999999999999999/a/b"),
+        (200, "named file".to_string())
+    );
+}
+
+#[test]
+fn router_dots_are_static() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<id:int>.json", |_, _, _| Response::new().with_body("json").build());
+        r.get("/<name>", |_, _, _| Response::new().with_body("name").build());
+        r.get("/robots.txt", |_, _, _| Response::new().with_body("robots").build());
+    }));
+
+    assert_eq!(get(&mut client, "/robots.txt"), (200, "robots".to_string()));
+    assert_eq!(get(&mut client, "/robotsXtxt"), (200, "name".to_string()));
+    assert_eq!(get(&mut client, "/42.json"), (200, "json".to_string()));
+    assert_eq!(get(&mut client, "/42xjson"), (200, "name".to_string()));
+}
+
+#[test]
+fn router_params_in_order() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/posts/<id:int>", |_, _, _| Response::new().with_body("id").build());
+        r.get("/posts/<slug>", |_, _, _| Response::new().with_body("slug").build());
+    }));
+
+    assert_eq!(get(&mut client, "/posts/42"), (200, "id".to_string()));
+    assert_eq!(get(&mut client, "/posts/hello"), (200, "slug".to_string()));
+}
+
+#[test]
+fn router_tree_before_patterns() {
+    let mut client = TestClient::new(Direkuta::new().route(|r| {
+        r.get("/<name:(.*)>", |_, _, _| Response::new().with_body("any").build());
+        r.get("/users/<id>", |_, _, _| Response::new().with_body("user").build());
+    }));
+
+    assert_eq!(get(&mut client, "/users/5"), (200, "user".to_string()));
+    assert_eq!(get(&mut client, "/users/5/posts"), (200, "any".to_string()));
+}
@@ -0,0 +1,112 @@
+extern crate direkuta;
+extern crate futures;
+extern crate tokio;
+
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use direkuta::prelude::*;
+use futures::sync::oneshot;
+use futures::{future, Future};
+use tokio::runtime::Runtime;
+
+fn app() -> Direkuta {
+    Direkuta::new().route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+    })
+}
+
+fn get(addr: std::net::SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    response
+}
+
+#[test]
+fn serve_existing_runtime() {
+    let mut runtime = Runtime::new().expect("To create runtime");
+
+    // Another task sharing the runtime.
+    let (task_tx, task_rx) = mpsc::channel();
+    runtime.spawn(future::lazy(move || {
+        task_tx.send(()).expect("To send from task");
+        Ok(())
+    }));
+
+    let bound = app().bind("127.0.0.1:0").expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = bound.serve_until(rx.map_err(|_| ())).expect("To create server");
+
+    let (done_tx, done_rx) = mpsc::channel();
+    runtime.spawn(server.then(move |res| {
+        done_tx.send(res.is_ok()).expect("To send server result");
+        Ok(())
+    }));
+
+    assert!(get(addr).ends_with("Hello World!"));
+    task_rx.recv().expect("Other task to run");
+
+    tx.send(()).expect("To send shutdown signal");
+    assert!(done_rx.recv().expect("Server to stop"));
+
+    runtime.shutdown_on_idle().wait().expect("To shutdown runtime");
+}
+
+#[test]
+fn run_until_uses_workers() {
+    let bound = Direkuta::config(|c| {
+        c.workers(1);
+    }).route(|r| {
+        r.get("/", |_, _, _| {
+            // Hold the worker so the other requests queue up behind it.
+            thread::sleep(Duration::from_millis(50));
+
+            Response::new()
+                .with_body(format!("{:?}", thread::current().id()))
+                .build()
+        });
+    }).bind("127.0.0.1:0")
+    .expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    let (tx, rx) = oneshot::channel::<()>();
+    let server = thread::spawn(move || bound.run_until(rx.map_err(|_| ())));
+
+    let clients = (0..4)
+        .map(|_| thread::spawn(move || get(addr)))
+        .collect::<Vec<_>>();
+    let mut threads = clients
+        .into_iter()
+        .map(|client| client.join().expect("Client to finish"))
+        .map(|response| response.rsplit("\r\n").next().unwrap_or_default().to_string())
+        .collect::<Vec<_>>();
+
+    threads.dedup();
+    assert_eq!(threads.len(), 1, "Requests ran on {:?}", threads);
+
+    tx.send(()).expect("To send shutdown signal");
+    server
+        .join()
+        .expect("Server thread to finish")
+        .expect("Server to run");
+}
+
+#[test]
+fn zero_workers_fail_to_build() {
+    match Direkuta::config(|c| c.workers(0)).build() {
+        Err(DireError::Config(_)) => {}
+        _ => panic!("Expected zero workers to be rejected"),
+    }
+}
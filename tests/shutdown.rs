@@ -0,0 +1,86 @@
+extern crate direkuta;
+extern crate futures;
+extern crate tokio;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::*;
+use futures::sync::oneshot;
+use futures::Future;
+use tokio::timer::Delay;
+
+fn slow(delay: Duration) -> Direkuta {
+    Direkuta::config(|c| {
+        c.shutdown_timeout(Duration::from_millis(500));
+    }).route(move |r| {
+        r.get("/slow", move |_, _, _| {
+            Box::new(
+                Delay::new(Instant::now() + delay)
+                    .map_err(|e| DireError::Other(e.to_string()))
+                    .map(|_| Response::new().with_body("Done").into_hyper()),
+            )
+        });
+    })
+}
+
+fn start(app: Direkuta) -> (SocketAddr, oneshot::Sender<()>, mpsc::Receiver<()>) {
+    let (tx, rx) = oneshot::channel::<()>();
+    let (done_tx, done_rx) = mpsc::channel();
+
+    let bound = app.bind("127.0.0.1:0").expect("To bind server");
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run_until(rx.map_err(|_| ())).expect("To start server");
+        done_tx.send(()).expect("To signal shutdown");
+    });
+
+    (addr, tx, done_rx)
+}
+
+fn request(addr: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+    stream
+}
+
+#[test]
+fn shutdown_drains_active_requests() {
+    let (addr, tx, done) = start(slow(Duration::from_millis(200)));
+
+    let mut stream = request(addr);
+    thread::sleep(Duration::from_millis(50));
+    tx.send(()).expect("To send shutdown signal");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Done"));
+
+    done.recv_timeout(Duration::from_secs(5))
+        .expect("Server to stop after draining");
+}
+
+#[test]
+fn shutdown_timeout_drops_requests() {
+    let (addr, tx, done) = start(slow(Duration::from_secs(30)));
+
+    let mut stream = request(addr);
+    thread::sleep(Duration::from_millis(50));
+    tx.send(()).expect("To send shutdown signal");
+
+    done.recv_timeout(Duration::from_secs(5))
+        .expect("Server to stop after the shutdown timeout");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.is_empty());
+}
@@ -0,0 +1,39 @@
+extern crate direkuta;
+
+use std::net::TcpListener;
+
+use direkuta::prelude::*;
+
+#[test]
+fn startup_invalid_address() {
+    match Direkuta::new().try_run("localhost") {
+        Err(DireError::Address(_)) => {}
+        other => panic!("Expected address error, got {:?}", other),
+    }
+}
+
+#[test]
+fn startup_address_in_use() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("To bind listener");
+    let addr = listener.local_addr().expect("To get address").to_string();
+
+    match Direkuta::new().try_run(&addr) {
+        Err(DireError::Bind(_)) => {}
+        other => panic!("Expected bind error, got {:?}", other),
+    }
+}
+
+#[test]
+fn startup_invalid_pattern() {
+    let result = Direkuta::new()
+        .route(|r| {
+            r.path("/users", |r| {
+                r.get("/<id:([0-9]+>", |_, _, _| Response::new().build());
+            });
+        }).try_run("127.0.0.1:0");
+
+    match result {
+        Err(DireError::Pattern(path, _)) => assert_eq!(path, "/<id:([0-9]+>"),
+        other => panic!("Expected pattern error, got {:?}", other),
+    }
+}
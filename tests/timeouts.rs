@@ -0,0 +1,164 @@
+extern crate direkuta;
+extern crate futures;
+extern crate tokio;
+
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::hyper::Body;
+use direkuta::prelude::*;
+use futures::{Future, Stream};
+use tokio::timer::{Delay, Interval};
+
+fn start() -> SocketAddr {
+    let bound = Direkuta::config(|c| {
+        c.keep_alive_timeout(Duration::from_millis(300));
+        c.header_read_timeout(Duration::from_millis(300));
+        c.max_header_size(8 * 1024);
+    }).route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+        r.get("/slow", |_, _, _| {
+            Box::new(
+                Delay::new(Instant::now() + Duration::from_millis(600))
+                    .map_err(|e| DireError::Other(e.to_string()))
+                    .map(|_| Response::new().with_body("Done").into_hyper()),
+            )
+        });
+        r.get("/stream", |_, _, _| {
+            // Each chunk takes longer than the keep-alive timeout.
+            let ticks = Interval::new(
+                Instant::now() + Duration::from_millis(500),
+                Duration::from_millis(500),
+            ).take(3)
+            .map(|_| "tick");
+
+            let mut res = Response::new().into_hyper();
+            *res.body_mut() = Body::wrap_stream(ticks);
+
+            Box::new(futures::future::ok(res))
+        });
+    }).bind("127.0.0.1:0")
+    .expect("To bind server");
+
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    addr
+}
+
+fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).expect("To connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("To set timeout");
+    stream
+}
+
+/// Wait for the server to close the connection, returning how long it took.
+fn closed(stream: &mut TcpStream) -> Duration {
+    let start = Instant::now();
+    let mut buf = [0; 1024];
+
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return start.elapsed(),
+            Ok(_) => {}
+        }
+    }
+}
+
+#[test]
+fn timeout_silent_client() {
+    let mut stream = connect(start());
+
+    assert!(closed(&mut stream) < Duration::from_secs(2));
+}
+
+#[test]
+fn timeout_slow_headers() {
+    let addr = start();
+    let mut stream = connect(addr);
+    let start = Instant::now();
+
+    // Keep sending, but too slowly to ever finish the headers in time.
+    for byte in b"GET / HTTP/1.1\r\nHost: localhost\r\nX-Slow: ".iter().cycle() {
+        if stream.write_all(&[*byte]).is_err() || start.elapsed() > Duration::from_secs(3) {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn timeout_idle_keep_alive() {
+    let mut stream = connect(start());
+
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    while !String::from_utf8_lossy(&response).ends_with("Hello World!") {
+        let n = stream.read(&mut buf).expect("To read response");
+        assert_ne!(n, 0);
+        response.extend_from_slice(&buf[..n]);
+    }
+
+    assert!(closed(&mut stream) < Duration::from_secs(2));
+}
+
+#[test]
+fn timeout_slow_handler() {
+    let mut stream = connect(start());
+
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.ends_with("Done"));
+}
+
+#[test]
+fn timeout_slow_body() {
+    let mut stream = connect(start());
+
+    stream
+        .write_all(b"GET /stream HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.contains("tick"), "{:?}", response);
+    assert!(response.ends_with("0\r\n\r\n"), "{:?}", response);
+}
+
+#[test]
+fn max_header_size() {
+    let mut stream = connect(start());
+
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: localhost\r\nX-Large: {}\r\nConnection: close\r\n\r\n",
+        "a".repeat(16 * 1024)
+    );
+    let _ = stream.write_all(request.as_bytes());
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+
+    assert!(response.starts_with("HTTP/1.1 431"));
+}
@@ -0,0 +1,303 @@
+#![cfg(feature = "tls")]
+
+extern crate direkuta;
+extern crate rcgen;
+extern crate rustls;
+extern crate webpki;
+
+use std::env;
+use std::fs;
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use direkuta::prelude::*;
+use rustls::internal::pemfile;
+
+/// Generates a self-signed certificate for localhost, returning the PEM paths.
+fn self_signed(name: &str) -> (PathBuf, PathBuf) {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+        .expect("To generate certificate");
+
+    let dir = env::temp_dir().join(format!("direkuta-tls-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).expect("To create certificate directory");
+
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+
+    fs::write(&cert_path, cert.serialize_pem().expect("To serialize certificate"))
+        .expect("To write certificate");
+    fs::write(&key_path, cert.serialize_private_key_pem()).expect("To write key");
+
+    (cert_path, key_path)
+}
+
+/// Generates a CA and a client certificate signed by it, returning the CA path and client PEMs.
+fn client_ca(name: &str) -> (PathBuf, String, String) {
+    let mut params = rcgen::CertificateParams::new(Vec::new());
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, "Direkuta Test CA");
+    let ca = rcgen::Certificate::from_params(params).expect("To generate CA");
+
+    let mut params = rcgen::CertificateParams::new(vec!["client.internal".to_string()]);
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, "client");
+    let client = rcgen::Certificate::from_params(params).expect("To generate client certificate");
+
+    let dir = env::temp_dir().join(format!("direkuta-tls-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).expect("To create certificate directory");
+
+    let ca_path = dir.join("ca.pem");
+    fs::write(&ca_path, ca.serialize_pem().expect("To serialize CA")).expect("To write CA");
+
+    (
+        ca_path,
+        client
+            .serialize_pem_with_signer(&ca)
+            .expect("To sign client certificate"),
+        client.serialize_private_key_pem(),
+    )
+}
+
+fn start(cert: &Path, key: &Path) -> SocketAddr {
+    start_with(cert, key, |_| {})
+}
+
+fn start_with(
+    cert: &Path,
+    key: &Path,
+    config: impl Fn(&mut builder::Config) + Send + Sync + 'static,
+) -> SocketAddr {
+    let cert = cert.to_str().unwrap().to_string();
+    let key = key.to_str().unwrap().to_string();
+
+    let bound = Direkuta::config(move |c| {
+        c.tls_certificate(cert.as_str());
+        c.tls_key(key.as_str());
+        config(c);
+    }).route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+        r.get("/large", |_, _, _| {
+            Response::new().with_body("a".repeat(256 * 1024)).build()
+        });
+        r.get("/peer", |req, _, _| {
+            let body = match req.peer_certificate() {
+                Some(cert) => format!(
+                    "{}|{}|{}",
+                    cert.subject(),
+                    cert.alt_names().join(","),
+                    cert.fingerprint()
+                ),
+                None => "anonymous".to_string(),
+            };
+
+            Response::new().with_body(body).build()
+        });
+    }).bind("127.0.0.1:0")
+    .expect("To bind server");
+
+    let addr = bound.local_addr().unwrap();
+
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    addr
+}
+
+fn client(cert: &Path, identity: Option<&(String, String)>) -> rustls::ClientSession {
+    let pem = fs::read(cert).expect("To read certificate");
+    let certs = pemfile::certs(&mut pem.as_slice()).expect("To parse certificate");
+
+    let mut config = rustls::ClientConfig::new();
+    config
+        .root_store
+        .add(&certs[0])
+        .expect("To trust certificate");
+
+    if let Some((cert, key)) = identity {
+        let certs = pemfile::certs(&mut cert.as_bytes()).expect("To parse client certificate");
+        let mut keys =
+            pemfile::pkcs8_private_keys(&mut key.as_bytes()).expect("To parse client key");
+
+        config.set_single_client_cert(certs, keys.remove(0));
+    }
+
+    let name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+    rustls::ClientSession::new(&Arc::new(config), name)
+}
+
+fn get(addr: SocketAddr, cert: &Path, path: &str) -> String {
+    get_as(addr, cert, None, path)
+}
+
+fn get_as(
+    addr: SocketAddr,
+    cert: &Path,
+    identity: Option<&(String, String)>,
+    path: &str,
+) -> String {
+    let mut session = client(cert, identity);
+    let mut socket = TcpStream::connect(addr).expect("To connect to server");
+    let mut stream = rustls::Stream::new(&mut session, &mut socket);
+
+    // Writing fails when the server rejects the handshake, which shows up as an empty response.
+    let _ = stream.write_all(
+        format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        ).as_bytes(),
+    );
+
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[test]
+fn tls_get() {
+    let (cert, key) = self_signed("get");
+    let addr = start(&cert, &key);
+
+    let response = get(addr, &cert, "/");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Hello World!"));
+}
+
+#[test]
+fn tls_get_large() {
+    let (cert, key) = self_signed("large");
+    let addr = start(&cert, &key);
+
+    let response = get(addr, &cert, "/large");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with(&"a".repeat(256 * 1024)));
+}
+
+#[test]
+fn tls_rejects_plain_http() {
+    let (cert, key) = self_signed("plain");
+    let addr = start(&cert, &key);
+
+    let mut socket = TcpStream::connect(addr).expect("To connect to server");
+    socket
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = Vec::new();
+    let _ = socket.read_to_end(&mut response);
+
+    assert!(!String::from_utf8_lossy(&response).contains("Hello World!"));
+}
+
+#[test]
+fn tls_missing_key() {
+    let (cert, _) = self_signed("missing");
+    let cert = cert.to_str().unwrap().to_string();
+
+    let result = Direkuta::config(move |c| {
+        c.tls_certificate(cert.as_str());
+    }).bind("127.0.0.1:0");
+
+    match result {
+        Err(DireError::Tls(_)) => {}
+        Err(e) => panic!("Expected TLS error, got {}", e),
+        Ok(_) => panic!("Expected TLS error"),
+    }
+}
+
+#[test]
+fn tls_client_cert() {
+    let (cert, key) = self_signed("client");
+    let (ca, client_cert, client_key) = client_ca("client");
+    let addr = start_with(&cert, &key, move |c| {
+        c.tls_client_ca(ca.to_str().unwrap());
+    });
+
+    let identity = (client_cert, client_key);
+    let response = get_as(addr, &cert, Some(&identity), "/peer");
+
+    let der = pemfile::certs(&mut identity.0.as_bytes()).unwrap().remove(0);
+    let expected = PeerCertificate::from_der(der.0).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with(&format!("CN=client|client.internal|{}", expected.fingerprint())));
+    assert_eq!(expected.fingerprint().len(), 64);
+}
+
+#[test]
+fn tls_client_cert_required() {
+    let (cert, key) = self_signed("required");
+    let (ca, _, _) = client_ca("required");
+    let addr = start_with(&cert, &key, move |c| {
+        c.tls_client_ca(ca.to_str().unwrap());
+    });
+
+    let response = get(addr, &cert, "/peer");
+
+    assert!(!response.contains("HTTP/1.1"));
+}
+
+#[test]
+fn tls_client_cert_untrusted() {
+    let (cert, key) = self_signed("untrusted");
+    let (ca, _, _) = client_ca("untrusted");
+    let (_, client_cert, client_key) = client_ca("untrusted-other");
+    let addr = start_with(&cert, &key, move |c| {
+        c.tls_client_ca(ca.to_str().unwrap());
+        c.tls_client_optional(true);
+    });
+
+    let response = get_as(addr, &cert, Some(&(client_cert, client_key)), "/peer");
+
+    assert!(!response.contains("HTTP/1.1"));
+}
+
+#[test]
+fn tls_client_cert_optional() {
+    let (cert, key) = self_signed("optional");
+    let (ca, _, _) = client_ca("optional");
+    let addr = start_with(&cert, &key, move |c| {
+        c.tls_client_ca(ca.to_str().unwrap());
+        c.tls_client_optional(true);
+    });
+
+    let response = get(addr, &cert, "/peer");
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("anonymous"));
+}
+
+#[test]
+fn tls_handshake_timeout() {
+    let (cert, key) = self_signed("handshake");
+    let addr = start_with(&cert, &key, |c| {
+        c.tls_handshake_timeout(Duration::from_millis(200));
+    });
+
+    // Connect but never start the handshake.
+    let mut socket = TcpStream::connect(addr).expect("To connect to server");
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("To set read timeout");
+
+    let start = Instant::now();
+    let mut response = Vec::new();
+    let _ = socket.read_to_end(&mut response);
+
+    assert!(response.is_empty());
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,143 @@
+#![cfg(unix)]
+
+extern crate direkuta;
+extern crate futures;
+
+use std::env;
+use std::fs;
+use std::io::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use direkuta::prelude::*;
+use futures::sync::oneshot;
+use futures::Future;
+
+fn app() -> Direkuta {
+    Direkuta::config(|c| {
+        c.unix_socket_mode(0o660);
+    }).route(|r| {
+        r.get("/", |_, _, _| {
+            Response::new().with_body("Hello World!").build()
+        });
+    })
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("direkuta-{}-{}.sock", process::id(), name))
+}
+
+fn get(bound: Bound, path: &Path) -> String {
+    thread::spawn(move || {
+        bound.run().expect("To run server");
+    });
+
+    let mut stream = UnixStream::connect(path).expect("To connect to server");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .expect("To set timeout");
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .expect("To write request");
+
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response);
+    response
+}
+
+#[test]
+fn unix_bind() {
+    let path = socket_path("bind");
+    let bound = app().bind_unix(&path).expect("To bind server");
+
+    let mode = fs::metadata(&path).expect("To read socket").permissions().mode();
+
+    assert_eq!(bound.local_addr(), None);
+    assert_eq!(mode & 0o777, 0o660);
+    assert!(get(bound, &path).ends_with("Hello World!"));
+}
+
+#[test]
+fn unix_bind_replaces_stale_socket() {
+    let path = socket_path("stale");
+    drop(UnixListener::bind(&path).expect("To bind stale socket"));
+
+    let bound = app().bind_unix(&path).expect("To bind server");
+
+    assert!(get(bound, &path).ends_with("Hello World!"));
+}
+
+#[test]
+fn unix_listen_existing() {
+    let path = socket_path("existing");
+    let listener = UnixListener::bind(&path).expect("To bind listener");
+
+    let bound = app().listen_unix(listener).expect("To use listener");
+
+    assert!(get(bound, &path).ends_with("Hello World!"));
+}
+
+#[test]
+fn unix_bind_keeps_other_files() {
+    let path = socket_path("file");
+    fs::write(&path, "data").expect("To write file");
+
+    match app().bind_unix(&path) {
+        Err(DireError::Bind(_)) => {}
+        Err(e) => panic!("Expected bind error, got {}", e),
+        Ok(_) => panic!("Expected bind error"),
+    }
+
+    assert_eq!(fs::read_to_string(&path).expect("To read file"), "data");
+
+    // Nothing is left behind under the temporary name either.
+    let dir = path.parent().unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap().to_string();
+    let leftovers = fs::read_dir(dir)
+        .expect("To read directory")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let other = entry.file_name().to_string_lossy().into_owned();
+            other != name && other.starts_with(&name)
+        })
+        .count();
+
+    assert_eq!(leftovers, 0);
+
+    fs::remove_file(&path).expect("To remove file");
+}
+
+#[test]
+fn unix_bind_removes_socket_on_shutdown() {
+    let path = socket_path("shutdown");
+    let bound = app().bind_unix(&path).expect("To bind server");
+    let (tx, rx) = oneshot::channel::<()>();
+
+    let server = thread::spawn(move || {
+        bound.run_until(rx.map_err(|_| ())).expect("To run server");
+    });
+
+    UnixStream::connect(&path).expect("To connect to server");
+    tx.send(()).expect("To stop server");
+    server.join().expect("To stop server");
+
+    assert!(!path.exists());
+}
+
+#[test]
+fn unix_bind_keeps_replaced_socket() {
+    let path = socket_path("replaced");
+    let bound = app().bind_unix(&path).expect("To bind server");
+
+    fs::remove_file(&path).expect("To remove socket");
+    let _other = UnixListener::bind(&path).expect("To bind other socket");
+
+    drop(bound);
+
+    assert!(path.exists());
+    fs::remove_file(&path).expect("To remove socket");
+}
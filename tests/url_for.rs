@@ -0,0 +1,149 @@
+extern crate direkuta;
+
+use direkuta::prelude::test::*;
+use direkuta::prelude::*;
+
+/// Serve the routes below, `/link` answers with the URL the closure builds.
+fn client(link: fn(&Urls) -> Result<String, DireError>) -> TestClient {
+    TestClient::new(Direkuta::new().route(move |r| {
+        r.get("/", |_, _, _| Response::new().build()).name("home");
+        r.get("/users/<id:int>", |_, _, _| Response::new().build())
+            .name("user");
+        r.path("/teams/<team>", |r| {
+            r.get("/members/<id:([0-9]+)>", |_, _, _| Response::new().build())
+                .name("member");
+        });
+        r.get("/files/abc\\.txt", |_, _, _| Response::new().build())
+            .name("file");
+        r.get("/robots.txt", |_, _, _| Response::new().build())
+            .name("robots");
+        r.get("/about/?", |_, _, _| Response::new().build()).name("about");
+        r.get("/u/<name>", |_, _, _| Response::new().build()).name("profile");
+        r.get("/static/<rest:path>", |_, _, _| Response::new().build())
+            .name("asset");
+        r.get("/link", move |_, s, _| {
+            let body = match link(s.get::<Urls>()) {
+                Ok(url) => url,
+                Err(DireError::Url(message)) => message,
+                Err(e) => e.to_string(),
+            };
+
+            Response::new().with_body(body).build()
+        });
+    }))
+}
+
+fn link(link: fn(&Urls) -> Result<String, DireError>) -> String {
+    client(link).get("/link").send().expect("To send request").text()
+}
+
+#[test]
+fn url_for_builds_paths() {
+    assert_eq!(link(|u| u.url_for("home", &[])), "/");
+    assert_eq!(link(|u| u.url_for("user", &[("id", "5")])), "/users/5");
+    assert_eq!(
+        link(|u| u.url_for("member", &[("id", "7"), ("team", "red")])),
+        "/teams/red/members/7"
+    );
+}
+
+#[test]
+fn url_for_checks_params() {
+    assert_eq!(
+        link(|u| u.url_for("user", &[("id", "five")])),
+        "`five` does not match the parameter `id` of the route `user`"
+    );
+    assert_eq!(
+        link(|u| u.url_for("member", &[("id", "7")])),
+        "The route `member` needs the parameter `team`"
+    );
+    assert_eq!(
+        link(|u| u.url_for("user", &[("id", "5"), ("page", "2")])),
+        "The route `user` has no parameter `page`"
+    );
+    assert_eq!(
+        link(|u| u.url_for("team", &[])),
+        "No route is named `team`"
+    );
+}
+
+#[test]
+fn url_for_plain_text() {
+    assert_eq!(link(|u| u.url_for("file", &[])), "/files/abc.txt");
+    assert_eq!(link(|u| u.url_for("robots", &[])), "/robots.txt");
+    assert_eq!(link(|u| u.url_for("about", &[])), "/about");
+}
+
+#[test]
+fn url_for_encodes_values() {
+    assert_eq!(
+        link(|u| u.url_for("profile", &[("name", "a b?x=1")])),
+        "/u/a%20b%3Fx%3D1"
+    );
+    assert_eq!(
+        link(|u| u.url_for("profile", &[("name", "a/b#c")])),
+        "/u/a%2Fb%23c"
+    );
+    assert_eq!(
+        link(|u| u.url_for("asset", &[("rest", "css/site a.css")])),
+        "/static/css/site%20a.css"
+    );
+}
+
+#[test]
+fn regex_outside_params_fails_to_build() {
+    let result = Direkuta::new()
+        .route(|r| {
+            r.get("/(a|b)/<id>", |_, _, _| Response::new().build()).name("page");
+        })
+        .build();
+
+    match result {
+        Err(DireError::Url(message)) => assert_eq!(
+            message,
+            "The route `/(a|b)/<id>` has a regex outside its parameters, its URL can't be built"
+        ),
+        _ => panic!("Expected the route to be reported"),
+    }
+}
+
+#[test]
+fn duplicate_names_fail_to_build() {
+    let result = Direkuta::new()
+        .route(|r| {
+            r.get("/a", |_, _, _| Response::new().build()).name("page");
+            r.get("/b", |_, _, _| Response::new().build()).name("page");
+        })
+        .build();
+
+    match result {
+        Err(DireError::Url(message)) => {
+            assert_eq!(message, "The route name `page` is used twice")
+        }
+        _ => panic!("Expected the duplicate name to be reported"),
+    }
+}
+
+#[cfg(feature = "html")]
+#[test]
+fn url_for_in_templates() {
+    use direkuta::prelude::html::*;
+
+    let mut tera = Tera::default();
+    tera.add_raw_template("link.html", r#"{{ url_for(name="user", id=5) | safe }}"#)
+        .expect("To add template");
+
+    let mut client = TestClient::new(
+        Direkuta::new().state(tera).route(|r| {
+            r.get("/users/<id:int>", |_, _, _| Response::new().build())
+                .name("user");
+            r.get("/", |_, s, _| {
+                Response::new()
+                    .with_body(s.get::<Tera>().render("link.html", &Context::new()).unwrap())
+                    .build()
+            });
+        }),
+    );
+
+    assert_eq!(client.get("/").send().unwrap().text(), "/users/5");
+}